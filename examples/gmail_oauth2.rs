@@ -33,10 +33,12 @@ fn main() {
         Err(e) => println!("Error selecting INBOX: {}", e)
     };
 
-    match imap_socket.fetch("2", "body[text]") {
-        Ok(lines) => {
-            for line in lines.iter() {
-                print!("{}", line);
+    match imap_socket.fetch(2, "body[text]") {
+        Ok(messages) => {
+            for message in messages.iter() {
+                if let Some(body) = message.text() {
+                    print!("{}", String::from_utf8_lossy(body));
+                }
             }
         },
         Err(e) => println!("Error Fetching email 2: {}", e)
@@ -0,0 +1,124 @@
+use super::envelope::Envelope;
+
+/// The MIME structure of a message, as returned by FETCH BODYSTRUCTURE or BODY.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BodyStructure {
+    /// A single part that is neither text nor an encapsulated message, e.g. an image.
+    Basic {
+        part: SinglePart
+    },
+    /// A `text/*` part.
+    Text {
+        part: SinglePart,
+        /// The size of the body in text lines.
+        lines: u32
+    },
+    /// A `message/rfc822` part, which encapsulates a complete message.
+    Message {
+        part: SinglePart,
+        envelope: Envelope,
+        body: Box<BodyStructure>,
+        /// The size of the encapsulated message in text lines.
+        lines: u32
+    },
+    /// A `multipart/*` part.
+    Multipart {
+        subtype: String,
+        bodies: Vec<BodyStructure>,
+        params: Vec<(String, String)>,
+        disposition: Option<Disposition>,
+        language: Vec<String>,
+        location: Option<String>
+    }
+}
+
+/// The fields of a non-multipart body part.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SinglePart {
+    /// The media type, e.g. `TEXT` or `image`, in the case sent by the server.
+    pub media_type: String,
+    /// The media subtype, e.g. `PLAIN` or `png`, in the case sent by the server.
+    pub media_subtype: String,
+    /// The content type parameters, such as `CHARSET` or `NAME`.
+    pub params: Vec<(String, String)>,
+    pub id: Option<String>,
+    pub description: Option<String>,
+    /// The content transfer encoding, e.g. `BASE64`.
+    pub encoding: String,
+    /// The size of the body in octets, in its transfer encoding.
+    pub size: u32,
+    pub md5: Option<String>,
+    pub disposition: Option<Disposition>,
+    pub language: Vec<String>,
+    pub location: Option<String>
+}
+
+/// The content disposition of a body part, e.g. `attachment` with a `FILENAME` parameter.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Disposition {
+    pub kind: String,
+    pub params: Vec<(String, String)>
+}
+
+impl BodyStructure {
+    /// Returns the lower case content type of this part, e.g. `text/plain` or `multipart/mixed`.
+    pub fn content_type(&self) -> String {
+        match *self {
+            BodyStructure::Basic { ref part } |
+            BodyStructure::Text { ref part, .. } |
+            BodyStructure::Message { ref part, .. } => {
+                format!("{}/{}", part.media_type, part.media_subtype).to_lowercase()
+            },
+            BodyStructure::Multipart { ref subtype, .. } => format!("multipart/{}", subtype).to_lowercase()
+        }
+    }
+
+    /// Returns the fields of a non-multipart part.
+    pub fn single_part(&self) -> Option<&SinglePart> {
+        match *self {
+            BodyStructure::Basic { ref part } |
+            BodyStructure::Text { ref part, .. } |
+            BodyStructure::Message { ref part, .. } => Some(part),
+            BodyStructure::Multipart { .. } => None
+        }
+    }
+
+    /// Returns all non-multipart parts together with their part numbers, which can be used to
+    /// fetch a single part, e.g. with `BODY[2.1]`.
+    pub fn parts(&self) -> Vec<(String, &BodyStructure)> {
+        let mut parts = Vec::new();
+        self.collect_parts("", &mut parts);
+        parts
+    }
+
+    /// Returns the part with the given part number, e.g. `"2.1"`.
+    pub fn part(&self, number: &str) -> Option<&BodyStructure> {
+        self.parts().into_iter().find(|&(ref n, _)| n == number).map(|(_, part)| part)
+    }
+
+    /// A multipart has no part number of its own and its bodies are numbered below the prefix. A
+    /// single part directly below the prefix (a message without multipart body) gets number 1.
+    fn collect_parts<'a>(&'a self, prefix: &str, parts: &mut Vec<(String, &'a BodyStructure)>) {
+        match *self {
+            BodyStructure::Multipart { ref bodies, .. } => {
+                for (i, body) in bodies.iter().enumerate() {
+                    let number = format!("{}{}", prefix, i + 1);
+                    body.collect_nested_parts(number, parts);
+                }
+            },
+            _ => self.collect_nested_parts(format!("{}1", prefix), parts)
+        }
+    }
+
+    fn collect_nested_parts<'a>(&'a self, number: String, parts: &mut Vec<(String, &'a BodyStructure)>) {
+        match *self {
+            BodyStructure::Multipart { .. } => self.collect_parts(&format!("{}.", number), parts),
+            BodyStructure::Message { ref body, .. } => {
+                let prefix = format!("{}.", number);
+                parts.push((number, self));
+                body.collect_parts(&prefix, parts);
+            },
+            _ => parts.push((number, self))
+        }
+    }
+}
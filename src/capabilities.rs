@@ -0,0 +1,59 @@
+use std::slice;
+
+/// The set of capabilities advertised by the server.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    capabilities: Vec<String>
+}
+
+impl Capabilities {
+    pub fn new(capabilities: Vec<String>) -> Capabilities {
+        Capabilities { capabilities: capabilities }
+    }
+
+    /// Checks if the server advertises a capability, e.g. `has("IDLE")` or `has("AUTH=PLAIN")`.
+    /// Capability names are compared case-insensitively.
+    pub fn has(&self, capability: &str) -> bool {
+        let capability = capability.to_uppercase();
+        self.capabilities.iter().any(|c| c.to_uppercase() == capability)
+    }
+
+    /// Returns the SASL mechanisms advertised with `AUTH=`, e.g. `PLAIN` or `XOAUTH2`.
+    pub fn auth_mechanisms(&self) -> Vec<&str> {
+        self.capabilities.iter()
+            .filter(|c| c.len() > 5 && c.is_char_boundary(5) && c[..5].to_uppercase() == "AUTH=")
+            .map(|c| &c[5..])
+            .collect()
+    }
+
+    pub fn iter(&self) -> slice::Iter<String> {
+        self.capabilities.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.capabilities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.capabilities.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn has() {
+        let capabilities = Capabilities::new(vec![String::from("IMAP4rev1"), String::from("IDLE")]);
+        assert!(capabilities.has("IDLE"), "IDLE should be advertised");
+        assert!(capabilities.has("imap4REV1"), "Capabilities should be compared case-insensitively");
+        assert!(!capabilities.has("MOVE"), "MOVE should not be advertised");
+    }
+
+    #[test]
+    fn auth_mechanisms() {
+        let capabilities = Capabilities::new(vec![String::from("IMAP4rev1"), String::from("AUTH=PLAIN"), String::from("auth=XOAUTH2")]);
+        assert!(capabilities.auth_mechanisms() == vec!["PLAIN", "XOAUTH2"], "Unexpected auth mechanisms");
+    }
+}
@@ -1,24 +1,69 @@
+use std::collections::{HashMap, HashSet};
 use std::net::{TcpStream, ToSocketAddrs};
 use openssl::ssl::{SslContext, SslStream};
-use std::io::{Read, Write};
-
-use super::mailbox::Mailbox;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::mem;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::mailbox::{Mailbox, MailboxStatus, StatusItem};
+use super::capabilities::Capabilities;
+use super::command::Command;
+use super::fetch::Fetch;
+use super::flag::{Flag, flag_list};
+use super::name::Name;
+use super::namespace::Namespaces;
+use super::search::SearchQuery;
+use super::sequence_set::SequenceSet;
+use super::store::StoreQuery;
 use super::authenticator::Authenticator;
-use super::parse::{parse_response_ok, parse_capability, parse_select_or_examine, parse_response, parse_authenticate_response};
-use super::error::{Error, Result};
+use super::response::{Response, ResponseCode, Status, UnsolicitedResponse};
+use super::parse::{parse_response, literal_needed, check_response, parse_capability, parse_capabilities_in, parse_select_or_examine, parse_fetches,
+	parse_names, parse_status, parse_unsolicited, parse_search, parse_vanished, parse_enabled, parse_namespaces, parse_id};
+use super::error::{Error, ParseError, Result};
+use super::utf7;
 
 static TAG_PREFIX: &'static str = "a";
 const INITIAL_TAG: u32 = 0;
 const CR: u8 = 0x0d;
 const LF: u8 = 0x0a;
+/// Servers may log out clients that idle for 30 minutes (RFC 2177), so IDLE is re-issued before.
+const IDLE_KEEPALIVE_SECS: u64 = 29 * 60;
+const NOOP_POLL_INTERVAL_SECS: u64 = 60;
 
 /// Stream to interface with the IMAP server. This interface is only for the command stream.
 pub struct Client<T> {
-	stream: T,
+	/// Responses are read through the buffer, while commands are written to the stream directly.
+	stream: BufReader<T>,
+	/// The part of a response read before a read failed, e.g. because it timed out during IDLE.
+	partial_response: PartialResponse,
 	tag: u32,
+	capabilities: Option<Capabilities>,
+	/// The extensions enabled with ENABLE, which last until the connection is closed.
+	enabled: Capabilities,
+	response_codes: Vec<ResponseCode>,
+	unsolicited_responses_tx: Sender<UnsolicitedResponse>,
+	/// Mailbox updates that the server sent without being asked for them, such as new messages
+	/// (EXISTS), expunged messages (EXPUNGE, or VANISHED once QRESYNC is enabled) or flag changes
+	/// (FETCH) that arrive during other commands. The responses accumulate until they are received.
+	pub unsolicited_responses: Receiver<UnsolicitedResponse>,
+	/// Sends and receives mailbox names as UTF-8 instead of modified UTF-7. Only set this if the
+	/// server accepts UTF-8 (UTF8=ACCEPT, RFC 6855); `enable` sets it once UTF8=ACCEPT is enabled.
+	pub utf8_mailbox_names: bool,
 	pub debug: bool
 }
 
+/// A response that has been read only in part. The next read continues where it stopped.
+#[derive(Default)]
+struct PartialResponse {
+	data: Vec<u8>,
+	/// The start of the line being read.
+	line_start: usize,
+	/// The number of octets of a literal that are still to be read.
+	literal_remaining: usize
+}
+
 impl Client<TcpStream> {
 	/// Creates a new client.
 	pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Client<TcpStream>> {
@@ -33,11 +78,12 @@ impl Client<TcpStream> {
 		}
 	}
 
-	/// This will upgrade a regular TCP connection to use SSL.
+	/// This will upgrade a regular TCP connection to use SSL. Capabilities advertised before
+	/// STARTTLS are discarded, as required by RFC 3501.
 	pub fn secure(mut self, ssl_context: SslContext) -> Result<Client<SslStream<TcpStream>>> {
 		// TODO This needs to be tested
 		try!(self.run_command_and_check_ok("STARTTLS"));
-		SslStream::connect(&ssl_context, self.stream)
+		SslStream::connect(&ssl_context, self.stream.into_inner())
 			.map(|s| Client::new(s))
 			.map_err(|e| Error::Ssl(e))
 	}
@@ -66,71 +112,136 @@ impl<T: Read+Write> Client<T> {
 
 	/// Creates a new client with the underlying stream.
 	pub fn new(stream: T) -> Client<T> {
+		let (tx, rx) = channel();
 		Client{
-			stream: stream,
+			stream: BufReader::new(stream),
+			partial_response: PartialResponse::default(),
 			tag: INITIAL_TAG,
+			capabilities: None,
+			enabled: Capabilities::default(),
+			response_codes: Vec::new(),
+			unsolicited_responses_tx: tx,
+			unsolicited_responses: rx,
+			utf8_mailbox_names: false,
 			debug: false
 		}
 	}
 
 	/// Authenticate will authenticate with the server, using the authenticator given.
 	pub fn authenticate<A: Authenticator>(&mut self, auth_type: &str, authenticator: A) -> Result<()> {
-		try!(self.run_command(&format!("AUTHENTICATE {}", auth_type).to_string()));
+		// Capabilities may change once authenticated.
+		self.capabilities = None;
+		try!(self.send_command(&Command::new("AUTHENTICATE").string(auth_type)));
 		self.do_auth_handshake(authenticator)
 	}
 
 	/// This func does the handshake process once the authenticate command is made.
 	fn do_auth_handshake<A: Authenticator>(&mut self, authenticator: A) -> Result<()> {
-		// TODO Clean up this code
+		let mut responses = Vec::new();
 		loop {
-			let line = try!(self.readline());
-
-			if line.starts_with(b"+") {
-				let data = try!(parse_authenticate_response(String::from_utf8(line).unwrap()));
-				let auth_response = authenticator.process(data);
-
-				try!(self.write_line(auth_response.into_bytes().as_slice()))
-			} else if line.starts_with(format!("{}{} ", TAG_PREFIX, self.tag).as_bytes()) {
-				try!(parse_response(vec![String::from_utf8(line).unwrap()]));
-				return Ok(());
-
-			} else {
-				let mut lines = try!(self.read_response());
-				lines.insert(0, String::from_utf8(line).unwrap());
-				try!(parse_response(lines.clone()));
-				return Ok(());
+			match try!(self.read_next_response()) {
+				Response::Continue(data) => {
+					let auth_response = authenticator.process(data);
+					try!(self.write_line(auth_response.into_bytes().as_slice()))
+				},
+				response => {
+					let done = self.is_completion(&response);
+					responses.push(response);
+					if done {
+						return check_response(responses).map(|_| ());
+					}
+				}
 			}
 		}
 	}
 
 	/// Log in to the IMAP server.
 	pub fn login(&mut self, username: & str, password: & str) -> Result<()> {
-		self.run_command_and_check_ok(&format!("LOGIN {} {}", username, password).to_string())
+		// Capabilities may change once authenticated.
+		self.capabilities = None;
+		self.run_and_check_ok(&Command::new("LOGIN").string(username).string(password))
 	}
 
 	/// Selects a mailbox
 	pub fn select(&mut self, mailbox_name: &str) -> Result<Mailbox> {
-		let lines = try!(
-			self.run_command_and_read_response(&format!("SELECT {}", mailbox_name).to_string())
-		);
-		parse_select_or_examine(lines)
+		let command = Command::new("SELECT").string(&self.encode_mailbox_name(mailbox_name));
+		let responses = try!(self.run_command_and_read_data(&command, &["EXISTS", "RECENT"]));
+		parse_select_or_examine(&responses)
+	}
+
+	/// Selects a mailbox and catches up with the changes since the last session (QRESYNC,
+	/// RFC 7162), given the UID validity and highest mod-sequence seen then and optionally the
+	/// known UIDs. Returns the mailbox, the UIDs expunged since and the messages changed since.
+	/// QRESYNC is enabled first unless it already is.
+	pub fn select_qresync(&mut self, mailbox_name: &str, uid_validity: u32, mod_seq: u64, known_uids: Option<&SequenceSet>) -> Result<(Mailbox, SequenceSet, Vec<Fetch>)> {
+		if !self.is_enabled("QRESYNC") {
+			try!(self.enable(&["QRESYNC"]));
+		}
+		let mut params = format!("{} {}", uid_validity, mod_seq);
+		// An empty set cannot be sent; without it, the server assumes all UIDs are known.
+		match known_uids {
+			Some(known_uids) if !known_uids.is_empty() => params.push_str(&format!(" {}", known_uids)),
+			_ => {}
+		}
+		let command = Command::new("SELECT").string(&self.encode_mailbox_name(mailbox_name)).raw(&format!("(QRESYNC ({}))", params));
+		let responses = try!(self.run_command_and_read_data(&command, &["EXISTS", "RECENT", "FETCH", "VANISHED"]));
+		Ok((try!(parse_select_or_examine(&responses)), try!(parse_vanished(&responses)), try!(parse_fetches(&responses))))
 	}
 
 	/// Examine is identical to Select, but the selected mailbox is identified as read-only
 	pub fn examine(&mut self, mailbox_name: &str) -> Result<Mailbox> {
-		let lines = try!(
-			self.run_command_and_read_response(&format!("EXAMINE {}", mailbox_name).to_string())
-		);
-		parse_select_or_examine(lines)
+		let command = Command::new("EXAMINE").string(&self.encode_mailbox_name(mailbox_name));
+		let responses = try!(self.run_command_and_read_data(&command, &["EXISTS", "RECENT"]));
+		parse_select_or_examine(&responses)
 	}
 
 	/// Fetch retreives data associated with a message in the mailbox.
-	pub fn fetch(&mut self, sequence_set: &str, query: &str) -> Result<Vec<String>> {
-		self.run_command_and_read_response(&format!("FETCH {} {}", sequence_set, query).to_string())
+	pub fn fetch<S: Into<SequenceSet>>(&mut self, sequence_set: S, query: &str) -> Result<Vec<Fetch>> {
+		let sequence_set = sequence_set.into();
+		if sequence_set.is_empty() {
+			return Ok(Vec::new());
+		}
+		let responses = try!(
+			self.run_command_and_read_data(&Command::new("FETCH").raw(&sequence_set.to_string()).raw(query), &["FETCH"])
+		);
+		parse_fetches(&responses)
+	}
+
+	pub fn uid_fetch<S: Into<SequenceSet>>(&mut self, uid_set: S, query: &str) -> Result<Vec<Fetch>> {
+		let uid_set = uid_set.into();
+		if uid_set.is_empty() {
+			return Ok(Vec::new());
+		}
+		let responses = try!(
+			self.run_command_and_read_data(&Command::new("UID FETCH").raw(&uid_set.to_string()).raw(query), &["FETCH"])
+		);
+		parse_fetches(&responses)
 	}
 
-	pub fn uid_fetch(&mut self, uid_set: &str, query: &str) -> Result<Vec<String>> {
-		self.run_command_and_read_response(&format!("UID FETCH {} {}", uid_set, query).to_string())
+	/// Fetches only the messages whose mod-sequence is higher than `mod_seq` (`CHANGEDSINCE`).
+	/// Requires CONDSTORE (RFC 7162).
+	pub fn fetch_changed_since<S: Into<SequenceSet>>(&mut self, sequence_set: S, query: &str, mod_seq: u64) -> Result<Vec<Fetch>> {
+		let sequence_set = sequence_set.into();
+		if sequence_set.is_empty() {
+			return Ok(Vec::new());
+		}
+		let modifier = format!("(CHANGEDSINCE {})", mod_seq);
+		let responses = try!(
+			self.run_command_and_read_data(&Command::new("FETCH").raw(&sequence_set.to_string()).raw(query).raw(&modifier), &["FETCH"])
+		);
+		parse_fetches(&responses)
+	}
+
+	pub fn uid_fetch_changed_since<S: Into<SequenceSet>>(&mut self, uid_set: S, query: &str, mod_seq: u64) -> Result<Vec<Fetch>> {
+		let uid_set = uid_set.into();
+		if uid_set.is_empty() {
+			return Ok(Vec::new());
+		}
+		let modifier = format!("(CHANGEDSINCE {})", mod_seq);
+		let responses = try!(
+			self.run_command_and_read_data(&Command::new("UID FETCH").raw(&uid_set.to_string()).raw(query).raw(&modifier), &["FETCH"])
+		);
+		parse_fetches(&responses)
 	}
 
 	/// Noop always succeeds, and it does nothing.
@@ -145,37 +256,80 @@ impl<T: Read+Write> Client<T> {
 
 	/// Create creates a mailbox with the given name.
 	pub fn create(&mut self, mailbox_name: &str) -> Result<()> {
-		self.run_command_and_check_ok(&format!("CREATE {}", mailbox_name).to_string())
+		let command = Command::new("CREATE").string(&self.encode_mailbox_name(mailbox_name));
+		self.run_and_check_ok(&command)
 	}
 
 	/// Delete permanently removes the mailbox with the given name.
 	pub fn delete(&mut self, mailbox_name: &str) -> Result<()> {
-		self.run_command_and_check_ok(&format!("DELETE {}", mailbox_name).to_string())
+		let command = Command::new("DELETE").string(&self.encode_mailbox_name(mailbox_name));
+		self.run_and_check_ok(&command)
 	}
 
 	/// Rename changes the name of a mailbox.
 	pub fn rename(&mut self, current_mailbox_name: &str, new_mailbox_name: &str) -> Result<()> {
-		self.run_command_and_check_ok(&format!("RENAME {} {}", current_mailbox_name, new_mailbox_name).to_string())
+		let command = Command::new("RENAME")
+			.string(&self.encode_mailbox_name(current_mailbox_name))
+			.string(&self.encode_mailbox_name(new_mailbox_name));
+		self.run_and_check_ok(&command)
 	}
 
 	/// Subscribe adds the specified mailbox name to the server's set of "active" or "subscribed"
 	/// mailboxes as returned by the LSUB command.
 	pub fn subscribe(&mut self, mailbox: &str) -> Result<()> {
-		self.run_command_and_check_ok(&format!("SUBSCRIBE {}", mailbox).to_string())
+		let command = Command::new("SUBSCRIBE").string(&self.encode_mailbox_name(mailbox));
+		self.run_and_check_ok(&command)
 	}
 
 	/// Unsubscribe removes the specified mailbox name from the server's set of "active" or "subscribed"
 	/// mailboxes as returned by the LSUB command.
 	pub fn unsubscribe(&mut self, mailbox: &str) -> Result<()> {
-		self.run_command_and_check_ok(&format!("UNSUBSCRIBE {}", mailbox).to_string())
+		let command = Command::new("UNSUBSCRIBE").string(&self.encode_mailbox_name(mailbox));
+		self.run_and_check_ok(&command)
 	}
 
-	/// Capability requests a listing of capabilities that the server supports.
-	pub fn capability(&mut self) -> Result<Vec<String>> {
-		let lines = try!(
-			self.run_command_and_read_response(&format!("CAPABILITY").to_string())
+	/// Capability requests a listing of capabilities that the server supports. The capabilities
+	/// are cached, including those sent by the server in the greeting or after LOGIN and
+	/// AUTHENTICATE, so the command is only sent if they are not known yet.
+	pub fn capability(&mut self) -> Result<Capabilities> {
+		if let Some(ref capabilities) = self.capabilities {
+			return Ok(capabilities.clone());
+		}
+		let responses = try!(
+			self.run_command_and_read_data(&Command::new("CAPABILITY"), &[])
 		);
-		parse_capability(lines)
+		let capabilities = try!(parse_capability(&responses));
+		self.capabilities = Some(capabilities.clone());
+		Ok(capabilities)
+	}
+
+	/// Checks if the server supports a capability, using the cached capabilities if known.
+	pub fn has_capability(&mut self, capability: &str) -> Result<bool> {
+		self.capability().map(|capabilities| capabilities.has(capability))
+	}
+
+	/// Enables extensions that change how the server behaves (ENABLE, RFC 5161), e.g. QRESYNC or
+	/// UTF8=ACCEPT, and returns those the server enabled. Once UTF8=ACCEPT is enabled, mailbox
+	/// names are sent and received as UTF-8.
+	pub fn enable(&mut self, capabilities: &[&str]) -> Result<Capabilities> {
+		let mut command = Command::new("ENABLE");
+		for capability in capabilities.iter() {
+			command = command.string(capability);
+		}
+		let responses = try!(self.run_command_and_read_data(&command, &[]));
+		let enabled = try!(parse_enabled(&responses));
+		if enabled.has("UTF8=ACCEPT") {
+			self.utf8_mailbox_names = true;
+		}
+		let mut all: Vec<String> = self.enabled.iter().cloned().collect();
+		all.extend(enabled.iter().filter(|capability| !self.enabled.has(capability)).cloned());
+		self.enabled = Capabilities::new(all);
+		Ok(enabled)
+	}
+
+	/// Checks if an extension has been enabled with `enable`. QRESYNC implies CONDSTORE.
+	pub fn is_enabled(&self, capability: &str) -> bool {
+		self.enabled.has(capability) || (capability.eq_ignore_ascii_case("CONDSTORE") && self.enabled.has("QRESYNC"))
 	}
 
 	/// Expunge permanently removes all messages that have the \Deleted flag set from the currently
@@ -184,6 +338,16 @@ impl<T: Read+Write> Client<T> {
 		self.run_command_and_check_ok("EXPUNGE")
 	}
 
+	/// Permanently removes the messages with the given UIDs, if they have the \Deleted flag set.
+	/// Other messages with the \Deleted flag set are kept. Requires UIDPLUS (RFC 4315).
+	pub fn uid_expunge<S: Into<SequenceSet>>(&mut self, uid_set: S) -> Result<()> {
+		let uid_set = uid_set.into();
+		if uid_set.is_empty() {
+			return Ok(());
+		}
+		self.run_and_check_ok(&Command::new("UID EXPUNGE").raw(&uid_set.to_string()))
+	}
+
 	/// Check requests a checkpoint of the currently selected mailbox.
 	pub fn check(&mut self) -> Result<()> {
 		self.run_command_and_check_ok("CHECK")
@@ -195,102 +359,421 @@ impl<T: Read+Write> Client<T> {
 		self.run_command_and_check_ok("CLOSE")
 	}
 
-	/// Store alters data associated with a message in the mailbox.
-	pub fn store(&mut self, sequence_set: &str, query: &str) -> Result<Vec<String>> {
-		self.run_command_and_read_response(&format!("STORE {} {}", sequence_set, query))
+	/// Store alters the flags of messages in the mailbox. Unless the query is silent, the
+	/// updated flags of each message are returned.
+	pub fn store<S: Into<SequenceSet>>(&mut self, sequence_set: S, query: &StoreQuery) -> Result<Vec<Fetch>> {
+		let sequence_set = sequence_set.into();
+		if sequence_set.is_empty() {
+			return Ok(Vec::new());
+		}
+		let arguments = try!(query.to_arguments());
+		let responses = try!(
+			self.run_command_and_read_data(&Command::new("STORE").raw(&sequence_set.to_string()).raw(&arguments), &["FETCH"])
+		);
+		parse_fetches(&responses)
+	}
+
+	pub fn uid_store<S: Into<SequenceSet>>(&mut self, uid_set: S, query: &StoreQuery) -> Result<Vec<Fetch>> {
+		let uid_set = uid_set.into();
+		if uid_set.is_empty() {
+			return Ok(Vec::new());
+		}
+		let arguments = try!(query.to_arguments());
+		let responses = try!(
+			self.run_command_and_read_data(&Command::new("UID STORE").raw(&uid_set.to_string()).raw(&arguments), &["FETCH"])
+		);
+		parse_fetches(&responses)
 	}
 
-	pub fn uid_store(&mut self, uid_set: &str, query: &str) -> Result<Vec<String>> {
-		self.run_command_and_read_response(&format!("UID STORE {} {}", uid_set, query))
+	/// Copy copies the specified message to the end of the specified destination mailbox. If the
+	/// server supports UIDPLUS, the UID validity of the destination mailbox and, for each copied
+	/// message, its UID and its new UID are returned.
+	pub fn copy<S: Into<SequenceSet>>(&mut self, sequence_set: S, mailbox_name: &str) -> Result<Option<(u32, Vec<(u32, u32)>)>> {
+		let sequence_set = sequence_set.into();
+		if sequence_set.is_empty() {
+			return Ok(None);
+		}
+		let command = Command::new("COPY").raw(&sequence_set.to_string()).string(&self.encode_mailbox_name(mailbox_name));
+		try!(self.run_and_check_ok(&command));
+		Ok(self.copy_uid())
 	}
 
-	/// Copy copies the specified message to the end of the specified destination mailbox.
-	pub fn copy(&mut self, sequence_set: &str, mailbox_name: &str) -> Result<()> {
-		self.run_command_and_check_ok(&format!("COPY {} {}", sequence_set, mailbox_name).to_string())
+	pub fn uid_copy<S: Into<SequenceSet>>(&mut self, uid_set: S, mailbox_name: &str) -> Result<Option<(u32, Vec<(u32, u32)>)>> {
+		let uid_set = uid_set.into();
+		if uid_set.is_empty() {
+			return Ok(None);
+		}
+		let command = Command::new("UID COPY").raw(&uid_set.to_string()).string(&self.encode_mailbox_name(mailbox_name));
+		try!(self.run_and_check_ok(&command));
+		Ok(self.copy_uid())
+	}
+
+	fn copy_uid(&self) -> Option<(u32, Vec<(u32, u32)>)> {
+		self.response_codes.iter().filter_map(|code| match *code {
+			ResponseCode::CopyUid(uid_validity, ref uids) => Some((uid_validity, uids.clone())),
+			_ => None
+		}).next()
+	}
+
+	/// Mv moves the specified messages to the end of the specified destination mailbox, with MOVE
+	/// (RFC 6851) if the server supports it. Otherwise the messages are copied and flagged as
+	/// \Deleted, and then expunged if the server supports UIDPLUS (RFC 4315), which allows to
+	/// expunge only these messages. Without UIDPLUS they are left flagged, since EXPUNGE would
+	/// remove all other \Deleted messages too. Returns whether the messages were removed from the
+	/// selected mailbox; if `false`, they were copied but are still in it, flagged as \Deleted.
+	pub fn mv<S: Into<SequenceSet>>(&mut self, sequence_set: S, mailbox_name: &str) -> Result<bool> {
+		let sequence_set = sequence_set.into();
+		if sequence_set.is_empty() {
+			return Ok(true);
+		}
+		if try!(self.has_capability("MOVE")) {
+			let command = Command::new("MOVE").raw(&sequence_set.to_string()).string(&self.encode_mailbox_name(mailbox_name));
+			try!(self.run_and_check_ok(&command));
+			return Ok(true);
+		}
+		// Sequence numbers change as messages are expunged, so the messages are moved by UID.
+		// UID SEARCH only returns the requested messages, unlike FETCH responses, which may
+		// include updates of other messages.
+		let uids = try!(self.uid_search(&SearchQuery::new().sequence_set(sequence_set)));
+		let uid_set: SequenceSet = uids.into_iter().collect();
+		self.move_by_copy(&uid_set, mailbox_name)
+	}
+
+	/// Identical to Mv, but the messages are specified by their unique identifiers.
+	pub fn uid_mv<S: Into<SequenceSet>>(&mut self, uid_set: S, mailbox_name: &str) -> Result<bool> {
+		let uid_set = uid_set.into();
+		if uid_set.is_empty() {
+			return Ok(true);
+		}
+		if try!(self.has_capability("MOVE")) {
+			let command = Command::new("UID MOVE").raw(&uid_set.to_string()).string(&self.encode_mailbox_name(mailbox_name));
+			try!(self.run_and_check_ok(&command));
+			return Ok(true);
+		}
+		self.move_by_copy(&uid_set, mailbox_name)
 	}
 
-	pub fn uid_copy(&mut self, uid_set: &str, mailbox_name: &str) -> Result<()> {
-		self.run_command_and_check_ok(&format!("UID COPY {} {}", uid_set, mailbox_name))
+	fn move_by_copy(&mut self, uid_set: &SequenceSet, mailbox_name: &str) -> Result<bool> {
+		if uid_set.is_empty() {
+			return Ok(true);
+		}
+		try!(self.uid_copy(uid_set, mailbox_name));
+		try!(self.uid_store(uid_set, &StoreQuery::add(&[Flag::Deleted]).silent()));
+		if !try!(self.has_capability("UIDPLUS")) {
+			return Ok(false);
+		}
+		try!(self.uid_expunge(uid_set));
+		Ok(true)
+	}
+
+	/// Returns the personal, other users' and shared namespaces of the server (NAMESPACE,
+	/// RFC 2342), whose prefixes can be used as the reference of `list`.
+	pub fn namespace(&mut self) -> Result<Namespaces> {
+		let responses = try!(self.run_command_and_read_data(&Command::new("NAMESPACE"), &[]));
+		let mut namespaces = try!(parse_namespaces(&responses));
+		if !self.utf8_mailbox_names {
+			for namespace in namespaces.personal.iter_mut().chain(namespaces.other_users.iter_mut()).chain(namespaces.shared.iter_mut()) {
+				if let Some(decoded) = utf7::decode(&namespace.prefix) {
+					namespace.prefix = decoded;
+				}
+			}
+		}
+		Ok(namespaces)
+	}
+
+	/// Identifies the client to the server and returns the server's identification (ID,
+	/// RFC 2971), e.g. its `name` and `version`. Some servers require ID before LOGIN.
+	pub fn id(&mut self, fields: Option<HashMap<String, String>>) -> Result<HashMap<String, String>> {
+		let command = match fields {
+			Some(fields) => {
+				let mut fields: Vec<(String, String)> = fields.into_iter().collect();
+				fields.sort();
+				Command::new("ID").group(fields.iter().map(|&(ref name, ref value)| {
+					Command::default().quoted(name).quoted(value)
+				}).collect())
+			},
+			None => Command::new("ID NIL")
+		};
+		let responses = try!(self.run_command_and_read_data(&command, &[]));
+		parse_id(&responses)
+	}
+
+	/// Append uploads a message to the end of the specified mailbox, optionally with flags
+	/// (e.g. `\Draft`) and an internal date such as `17-Jul-1996 02:44:25 -0700`. If the server
+	/// supports UIDPLUS, the UID validity of the mailbox and the UID of the new message are
+	/// returned.
+	pub fn append(&mut self, mailbox_name: &str, flags: &[Flag], internal_date: Option<&str>, message: &[u8]) -> Result<Option<(u32, u32)>> {
+		let mut command = Command::new("APPEND").string(&self.encode_mailbox_name(mailbox_name));
+		if !flags.is_empty() {
+			command = command.raw(&try!(flag_list(flags)));
+		}
+		if let Some(internal_date) = internal_date {
+			command = command.quoted(internal_date);
+		}
+		try!(self.run_and_check_ok(&command.literal(message)));
+
+		Ok(self.response_codes.iter().filter_map(|code| match *code {
+			ResponseCode::AppendUid(uid_validity, uid) => Some((uid_validity, uid)),
+			_ => None
+		}).next())
 	}
 
 	/// The LIST command returns a subset of names from the complete set
 	/// of all names available to the client.
-	pub fn list(&mut self, reference_name: &str, mailbox_search_pattern: &str) -> Result<Vec<String>> {
-		self.run_command_and_parse(&format!("LIST {} {}", reference_name, mailbox_search_pattern))
+	pub fn list(&mut self, reference_name: &str, mailbox_search_pattern: &str) -> Result<Vec<Name>> {
+		let command = Command::new("LIST")
+			.string(&self.encode_mailbox_name(reference_name))
+			.list_mailbox(&self.encode_mailbox_name(mailbox_search_pattern));
+		let responses = try!(self.run_command_and_read_data(&command, &[]));
+		let names = try!(parse_names(&responses, "LIST"));
+		Ok(self.decode_names(names))
 	}
 
 	/// The LSUB command returns a subset of names from the set of names
 	/// that the user has declared as being "active" or "subscribed".
-	pub fn lsub(&mut self, reference_name: &str, mailbox_search_pattern: &str) -> Result<Vec<String>> {
-		self.run_command_and_parse(&format!("LSUB {} {}", reference_name, mailbox_search_pattern))
+	pub fn lsub(&mut self, reference_name: &str, mailbox_search_pattern: &str) -> Result<Vec<Name>> {
+		let command = Command::new("LSUB")
+			.string(&self.encode_mailbox_name(reference_name))
+			.list_mailbox(&self.encode_mailbox_name(mailbox_search_pattern));
+		let responses = try!(self.run_command_and_read_data(&command, &[]));
+		let names = try!(parse_names(&responses, "LSUB"));
+		Ok(self.decode_names(names))
 	}
 
 	/// The STATUS command requests the status of the indicated mailbox.
-	pub fn status(&mut self, mailbox_name: &str, status_data_items: &str) -> Result<Vec<String>> {
-		self.run_command_and_parse(&format!("STATUS {} {}", mailbox_name, status_data_items))
+	pub fn status(&mut self, mailbox_name: &str, items: &[StatusItem]) -> Result<MailboxStatus> {
+		let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+		let command = Command::new("STATUS")
+			.string(&self.encode_mailbox_name(mailbox_name))
+			.raw(&format!("({})", items.join(" ")));
+		let responses = try!(self.run_command_and_read_data(&command, &[]));
+		parse_status(&responses)
+	}
+
+	/// The SEARCH command searches the mailbox for messages that match the query and returns
+	/// their sequence numbers.
+	pub fn search(&mut self, query: &SearchQuery) -> Result<HashSet<u32>> {
+		let command = Command::new("SEARCH").command(try!(query.to_command()));
+		let responses = try!(self.run_command_and_read_data(&command, &[]));
+		parse_search(&responses)
+	}
+
+	/// Identical to Search, but returns the unique identifiers of the messages.
+	pub fn uid_search(&mut self, query: &SearchQuery) -> Result<HashSet<u32>> {
+		let command = Command::new("UID SEARCH").command(try!(query.to_command()));
+		let responses = try!(self.run_command_and_read_data(&command, &[]));
+		parse_search(&responses)
+	}
+
+	/// Encodes a mailbox name or pattern in modified UTF-7, unless UTF-8 names are used.
+	fn encode_mailbox_name(&self, name: &str) -> String {
+		if self.utf8_mailbox_names {
+			name.to_string()
+		} else {
+			utf7::encode(name)
+		}
 	}
 
-	/// Runs a command and checks if it returns OK.
+	/// Decodes the names returned by LIST or LSUB. Names that are not valid modified UTF-7 are
+	/// kept as sent by the server.
+	fn decode_names(&self, mut names: Vec<Name>) -> Vec<Name> {
+		if !self.utf8_mailbox_names {
+			for name in names.iter_mut() {
+				if let Some(decoded) = utf7::decode(&name.name) {
+					name.name = decoded;
+				}
+			}
+		}
+		names
+	}
+
+	/// Runs a command and checks if it returns OK. Mailbox updates sent along with the response
+	/// are delivered through `unsolicited_responses`.
 	pub fn run_command_and_check_ok(&mut self, command: &str) -> Result<()> {
-		let lines = try!(self.run_command_and_read_response(command));
-		parse_response_ok(lines)
+		self.run_and_check_ok(&Command::new(command))
+	}
+
+	fn run_and_check_ok(&mut self, command: &Command) -> Result<()> {
+		try!(self.run_command_and_read_data(command, &[]));
+		Ok(())
+	}
+
+	/// Runs a command, checks the status response and returns the untagged responses. EXISTS,
+	/// RECENT, EXPUNGE and FETCH responses are returned only if their name is in `solicited`;
+	/// otherwise they are delivered through `unsolicited_responses`, even if the command fails.
+	fn run_command_and_read_data(&mut self, command: &Command, solicited: &[&str]) -> Result<Vec<Response>> {
+		try!(self.send_command(command));
+		let responses = try!(self.read_response());
+		let mut data = Vec::new();
+		for response in responses.into_iter() {
+			let unsolicited = match response {
+				Response::Data { ref name, .. } => !solicited.contains(&&name[..]),
+				_ => false
+			};
+			if unsolicited && try!(self.deliver_unsolicited(&response)) {
+				continue;
+			}
+			data.push(response);
+		}
+		check_response(data)
+	}
+
+	/// Runs a command, checks the status response and returns all untagged responses.
+	pub fn run_command_and_parse(&mut self, command: &str) -> Result<Vec<Response>> {
+		let responses = try!(self.run_command_and_read_response(command));
+		check_response(responses)
 	}
 
-	// Run a command and parse the status response.
-	pub fn run_command_and_parse(&mut self, command: &str) -> Result<Vec<String>> {
-		let lines = try!(self.run_command_and_read_response(command));
-		parse_response(lines)
+	/// Returns the response codes of the status responses, tagged and untagged, that the server
+	/// sent for the last command, e.g. `ResponseCode::ReadOnly` after EXAMINE or
+	/// `ResponseCode::Alert` when the server has a message for the user. The response code of a
+	/// failed command is also part of the returned error.
+	pub fn response_codes(&self) -> &[ResponseCode] {
+		&self.response_codes
 	}
 
-	/// Runs any command passed to it.
+	/// Runs any command passed to it. The command is sent as is, so any arguments must already
+	/// be encoded.
 	pub fn run_command(&mut self, untagged_command: &str) -> Result<()> {
-		let command = self.create_command(untagged_command.to_string());
-		self.write_line(command.into_bytes().as_slice())
+		self.send_command(&Command::new(untagged_command))
 	}
 
-	pub fn run_command_and_read_response(&mut self, untagged_command: &str) -> Result<Vec<String>> {
+	/// Sends a command, and the data of its literals whenever the server is ready for them.
+	fn send_command(&mut self, command: &Command) -> Result<()> {
+		self.response_codes.clear();
+		let mut prefix = self.create_command(String::new());
+		for &(ref line, ref data) in command.literal_lines.iter() {
+			try!(self.write_line(format!("{}{}", prefix, line).as_bytes()));
+			prefix.clear();
+			try!(self.send_literal(data));
+		}
+		self.write_line(format!("{}{}", prefix, command.line).as_bytes())
+	}
+
+	/// Runs a command and returns all responses, up to and including the tagged status response.
+	pub fn run_command_and_read_response(&mut self, untagged_command: &str) -> Result<Vec<Response>> {
 		try!(self.run_command(untagged_command));
 		self.read_response()
 	}
 
-	fn read_response(&mut self) -> Result<Vec<String>> {
-		let mut found_tag_line = false;
-		let start_str = format!("{}{} ", TAG_PREFIX, self.tag);
-		let mut lines: Vec<String> = Vec::new();
-
-		while !found_tag_line {
-			let raw_data = try!(self.readline());
-			let line = String::from_utf8(raw_data).unwrap();
-			lines.push(line.clone());
-			if (&*line).starts_with(&*start_str) {
-				found_tag_line = true;
+	/// Reads the responses up to and including the tagged status response that completes the
+	/// current command. Status responses tagged for earlier commands are skipped. If a response
+	/// cannot be parsed, the rest are still read, so that they are not taken for responses to the
+	/// next command, and then the parse error is returned.
+	fn read_response(&mut self) -> Result<Vec<Response>> {
+		let mut responses = Vec::new();
+		let mut error = None;
+		loop {
+			let data = try!(self.read_response_line());
+			let response = match self.parse_next_response(&data) {
+				Ok(response) => response,
+				Err(e) => {
+					if data.starts_with(format!("{}{} ", TAG_PREFIX, self.tag).as_bytes()) {
+						return Err(error.unwrap_or(e));
+					}
+					error = error.or(Some(e));
+					continue;
+				}
+			};
+			if self.is_completion(&response) {
+				if let Some(e) = error {
+					return Err(e);
+				}
+				responses.push(response);
+				return Ok(responses);
 			}
+			if let Response::Status { tag: Some(_), .. } = response {
+				continue;
+			}
+			responses.push(response);
+		}
+	}
+
+	/// Reads and parses the next response from the server.
+	fn read_next_response(&mut self) -> Result<Response> {
+		let data = try!(self.read_response_line());
+		self.parse_next_response(&data)
+	}
+
+	/// Parses a response read from the server and keeps its capabilities and response code.
+	fn parse_next_response(&mut self, data: &[u8]) -> Result<Response> {
+		let response = try!(parse_response(data));
+		if let Some(capabilities) = parse_capabilities_in(&response) {
+			self.capabilities = Some(capabilities);
 		}
+		if let Some(code) = response.code() {
+			self.response_codes.push(code.clone());
+		}
+		Ok(response)
+	}
 
-		Ok(lines)
+	/// Checks if the response is the tagged status response completing the current command.
+	fn is_completion(&self, response: &Response) -> bool {
+		match *response {
+			Response::Status { tag: Some(ref tag), .. } => *tag == format!("{}{}", TAG_PREFIX, self.tag),
+			_ => false
+		}
 	}
 
 	fn read_greeting(&mut self) -> Result<()> {
-		try!(self.readline());
+		match try!(self.read_next_response()) {
+			Response::Status { tag: None, status: Status::Ok, .. } |
+			Response::Status { tag: None, status: Status::PreAuth, .. } => Ok(()),
+			Response::Status { tag: None, status: Status::Bye, code, information } => Err(Error::ByeResponse(code, information)),
+			_ => Err(Error::Parse(ParseError::StatusResponse(String::from("Unexpected greeting"))))
+		}
+	}
+
+	/// Reads a single response from the server. If the response contains literals (`{N}\r\n`),
+	/// exactly N octets are read for each of them and kept as part of the same response. If a
+	/// read fails, e.g. because it timed out, the data read so far is kept and the next call
+	/// continues the same response.
+	fn read_response_line(&mut self) -> Result<Vec<u8>> {
+		loop {
+			if self.partial_response.literal_remaining > 0 {
+				try!(self.read_literal());
+				self.partial_response.line_start = self.partial_response.data.len();
+			}
+			try!(self.readline());
+			match literal_needed(&self.partial_response.data) {
+				Some(size) => {
+					self.partial_response.literal_remaining = size;
+					self.partial_response.line_start = self.partial_response.data.len();
+				},
+				None => return Ok(mem::replace(&mut self.partial_response, PartialResponse::default()).data)
+			}
+		}
+	}
+
+	fn read_literal(&mut self) -> Result<()> {
+		// The buffer grows with the data actually received, not with the announced size.
+		let start = self.partial_response.data.len();
+		let remaining = self.partial_response.literal_remaining as u64;
+		let result = (&mut self.stream).take(remaining).read_to_end(&mut self.partial_response.data);
+		self.partial_response.literal_remaining -= self.partial_response.data.len() - start;
+		try!(result);
+		if self.partial_response.literal_remaining > 0 {
+			return Err(Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed by server")));
+		}
 		Ok(())
 	}
 
-	fn readline(&mut self) -> Result<Vec<u8>> {
-		let mut line_buffer: Vec<u8> = Vec::new();
-		while line_buffer.len() < 2 || (line_buffer[line_buffer.len()-1] != LF && line_buffer[line_buffer.len()-2] != CR) {
-			let byte_buffer: &mut [u8] = &mut [0];
-			try!(self.stream.read(byte_buffer));
-			line_buffer.push(byte_buffer[0]);
+	/// Reads the rest of the current line of the response.
+	fn readline(&mut self) -> Result<()> {
+		let partial = &mut self.partial_response;
+		// Only CRLF ends a line; a lone CR or LF may be part of 8-bit text.
+		while !partial.data[partial.line_start..].ends_with(&[CR, LF]) {
+			if try!(self.stream.read_until(LF, &mut partial.data)) == 0 {
+				return Err(Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed by server")));
+			}
 		}
 
 		if self.debug {
-			let mut line = line_buffer.clone();
 			// Remove CRLF
-			line.truncate(line_buffer.len()-2);
-			print!("S: {}\n", String::from_utf8(line).unwrap());
+			print!("S: {}\n", String::from_utf8_lossy(&partial.data[partial.line_start..partial.data.len()-2]));
 		}
 
-		Ok(line_buffer)
+		Ok(())
 	}
 
 	fn create_command(&mut self, command: String) -> String {
@@ -299,32 +782,333 @@ impl<T: Read+Write> Client<T> {
 		return command;
 	}
 
+	/// Delivers a mailbox update through `unsolicited_responses`. Returns false if the response
+	/// is not a mailbox update.
+	fn deliver_unsolicited(&mut self, response: &Response) -> Result<bool> {
+		match try!(parse_unsolicited(response)) {
+			Some(unsolicited_response) => {
+				let _ = self.unsolicited_responses_tx.send(unsolicited_response);
+				Ok(true)
+			},
+			None => Ok(false)
+		}
+	}
+
+	/// Reads the responses up to the tagged completion and delivers the mailbox updates among
+	/// them, even if the command fails. Returns true if there were any.
+	fn read_updates(&mut self) -> Result<bool> {
+		let responses = try!(self.read_response());
+		let mut updated = false;
+		let mut rest = Vec::new();
+		for response in responses.into_iter() {
+			if try!(self.deliver_unsolicited(&response)) {
+				updated = true;
+			} else {
+				rest.push(response);
+			}
+		}
+		try!(check_response(rest));
+		Ok(updated)
+	}
+
+	/// Waits for the continuation request of the server, delivering any mailbox updates that
+	/// arrive first. Fails if the server completes the command instead.
+	fn wait_for_continuation(&mut self) -> Result<()> {
+		loop {
+			let response = try!(self.read_next_response());
+			if let Response::Continue(_) = response {
+				return Ok(());
+			}
+			if self.is_completion(&response) {
+				try!(check_response(vec![response]));
+				return Err(Error::Parse(ParseError::StatusResponse(String::from("Command completed before continuation request"))));
+			}
+			try!(self.deliver_unsolicited(&response));
+		}
+	}
+
+	/// Sends the data of a literal, which the command line sent before has announced with `{N}`,
+	/// once the server is ready for it.
+	fn send_literal(&mut self, data: &[u8]) -> Result<()> {
+		try!(self.wait_for_continuation());
+		try!(self.stream.get_mut().write_all(data));
+		if self.debug {
+			print!("C: <literal of {} octets>\n", data.len());
+		}
+		Ok(())
+	}
+
 	fn write_line(&mut self, buf: &[u8]) -> Result<()> {
-		try!(self.stream.write_all(buf));
-		try!(self.stream.write_all(&[CR, LF]));
+		let mut line = Vec::with_capacity(buf.len() + 2);
+		line.extend_from_slice(buf);
+		line.extend_from_slice(&[CR, LF]);
+		try!(self.stream.get_mut().write_all(&line));
+		try!(self.stream.get_mut().flush());
 		if self.debug {
-			print!("C: {}\n", String::from_utf8(buf.to_vec()).unwrap());
+			print!("C: {}\n", String::from_utf8_lossy(buf));
 		}
 		Ok(())
 	}
 }
 
+/// A stream whose reads can time out, so that IDLE can be interrupted to keep the connection alive.
+pub trait SetReadTimeout {
+	fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()>;
+}
+
+impl SetReadTimeout for TcpStream {
+	fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+		TcpStream::set_read_timeout(self, timeout).map_err(Error::Io)
+	}
+}
+
+impl SetReadTimeout for SslStream<TcpStream> {
+	fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+		self.get_ref().set_read_timeout(timeout).map_err(Error::Io)
+	}
+}
+
+impl<T: Read+Write+SetReadTimeout> Client<T> {
+	/// Returns a handle to wait for mailbox updates with IDLE (RFC 2177). If the server does not
+	/// support IDLE, the handle polls with NOOP instead.
+	pub fn idle(&mut self) -> Result<IdleHandle<T>> {
+		let supported = try!(self.has_capability("IDLE"));
+		Ok(IdleHandle {
+			client: self,
+			supported: supported,
+			keepalive: Duration::from_secs(IDLE_KEEPALIVE_SECS),
+			poll_interval: Duration::from_secs(NOOP_POLL_INTERVAL_SECS)
+		})
+	}
+
+	/// Reads the next response, failing with a `WouldBlock` or `TimedOut` error if none arrives
+	/// within the timeout. A response that is only partly read is continued by the next read.
+	fn read_next_response_within(&mut self, timeout: Duration) -> Result<Response> {
+		try!(self.stream.get_mut().set_read_timeout(Some(timeout)));
+		let response = self.read_next_response();
+		try!(self.stream.get_mut().set_read_timeout(None));
+		response
+	}
+}
+
+/// Waits for mailbox updates, such as new messages, which are delivered through
+/// `Client::unsolicited_responses`.
+///
+/// ```no_run
+/// # use imap::client::Client;
+/// # use std::time::Duration;
+/// let mut client = Client::connect(("imap.example.com", 143)).unwrap();
+/// client.login("username", "password").unwrap();
+/// client.select("INBOX").unwrap();
+/// if client.idle().unwrap().wait_timeout(Duration::from_secs(300)).unwrap() {
+///     for update in client.unsolicited_responses.try_iter() {
+///         println!("{:?}", update);
+///     }
+/// }
+/// ```
+pub struct IdleHandle<'a, T: 'a> {
+	client: &'a mut Client<T>,
+	supported: bool,
+	keepalive: Duration,
+	poll_interval: Duration
+}
+
+impl<'a, T: Read+Write+SetReadTimeout+'a> IdleHandle<'a, T> {
+	/// Sets the interval after which IDLE is ended and issued again, 29 minutes by default.
+	pub fn set_keepalive(&mut self, interval: Duration) {
+		self.keepalive = interval;
+	}
+
+	/// Sets the interval of the NOOP commands used if the server does not support IDLE, one
+	/// minute by default.
+	pub fn set_poll_interval(&mut self, interval: Duration) {
+		self.poll_interval = interval;
+	}
+
+	/// Blocks until the server reports a mailbox update.
+	pub fn wait(&mut self) -> Result<()> {
+		self.wait_until(None).map(|_| ())
+	}
+
+	/// Blocks until the server reports a mailbox update or the timeout elapses. Returns false if
+	/// the timeout elapsed.
+	pub fn wait_timeout(&mut self, timeout: Duration) -> Result<bool> {
+		self.wait_until(Some(Instant::now() + timeout))
+	}
+
+	fn wait_until(&mut self, deadline: Option<Instant>) -> Result<bool> {
+		loop {
+			let mut interval = if self.supported { self.keepalive } else { self.poll_interval };
+			if let Some(deadline) = deadline {
+				let now = Instant::now();
+				if now >= deadline {
+					return Ok(false);
+				}
+				if deadline - now < interval {
+					interval = deadline - now;
+				}
+			}
+			let updated = if self.supported {
+				try!(self.idle_for(interval))
+			} else {
+				try!(self.poll_after(interval))
+			};
+			if updated {
+				return Ok(true);
+			}
+		}
+	}
+
+	/// Idles until a mailbox update arrives or the interval elapses, then sends DONE. DONE is
+	/// also sent if reading fails, so that the connection can still be used for other commands.
+	fn idle_for(&mut self, interval: Duration) -> Result<bool> {
+		let client = &mut *self.client;
+		try!(client.send_command(&Command::new("IDLE")));
+		try!(client.wait_for_continuation());
+
+		let end = Instant::now() + interval;
+		let mut updated = false;
+		let mut error = None;
+		while !updated {
+			let now = Instant::now();
+			if now >= end {
+				break;
+			}
+			match client.read_next_response_within(end - now) {
+				Ok(response) => {
+					if client.is_completion(&response) {
+						// The server ended IDLE by itself.
+						try!(check_response(vec![response]));
+						return Ok(false);
+					}
+					match client.deliver_unsolicited(&response) {
+						Ok(delivered) => updated = delivered,
+						Err(e) => error = Some(e)
+					}
+				},
+				Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => break,
+				Err(e) => error = Some(e)
+			}
+			if error.is_some() {
+				break;
+			}
+		}
+
+		let done = client.write_line(b"DONE").and_then(|_| client.read_updates());
+		match error {
+			Some(e) => Err(e),
+			None => done.map(|done_updated| updated || done_updated)
+		}
+	}
+
+	/// Waits for the interval, then sends NOOP to learn about mailbox updates.
+	fn poll_after(&mut self, interval: Duration) -> Result<bool> {
+		thread::sleep(interval);
+		let client = &mut *self.client;
+		try!(client.send_command(&Command::new("NOOP")));
+		client.read_updates()
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
 	use super::super::mock_stream::MockStream;
-	use super::super::mailbox::Mailbox;
+	use super::super::mailbox::{Mailbox, MailboxStatus, StatusItem};
+	use super::super::name::NameAttribute;
+	use super::super::response::{Response, ResponseCode, Status, UnsolicitedResponse};
+	use super::super::error::Error;
+	use super::super::search::{Date, SearchQuery};
+	use super::super::store::StoreQuery;
 
 	#[test]
 	fn read_response() {
 		let response = "a0 OK Logged in.\r\n";
-		let expected_response: Vec<String> = vec![response.to_string()];
+		let expected_response = vec![Response::Status {
+			tag: Some(String::from("a0")),
+			status: Status::Ok,
+			code: None,
+			information: String::from("Logged in.")
+		}];
 		let mock_stream = MockStream::new(response.as_bytes().to_vec());
 		let mut client = Client::new(mock_stream);
 		let actual_response = client.read_response().unwrap();
 		assert!(expected_response == actual_response, "expected response doesn't equal actual");
 	}
 
+	#[test]
+	fn read_response_with_literal() {
+		let response = "* 1 FETCH (BODY[] {13}\r\nHello\r\nWorld! UID 7)\r\n\
+			a0 OK FETCH completed\r\n";
+		let expected_response = b"* 1 FETCH (BODY[] {13}\r\nHello\r\nWorld! UID 7)\r\n".to_vec();
+		let mock_stream = MockStream::new(response.as_bytes().to_vec());
+		let mut client = Client::new(mock_stream);
+		let actual_response = client.read_response_line().unwrap();
+		assert!(expected_response == actual_response, "expected response doesn't equal actual");
+	}
+
+	#[test]
+	fn read_response_truncated_literal() {
+		let response = b"* 1 FETCH (BODY[] {4000000000}\r\nSubject: Hi\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		match client.read_response_line() {
+			Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => {},
+			result => panic!("Truncated literal should fail: {:?}", result)
+		}
+	}
+
+	#[test]
+	fn read_response_8bit() {
+		let response = b"* 1 FETCH (BODY[HEADER] {18}\r\nSubject: Gr\xfc\xdfe\r\n\r\n ENVELOPE (NIL \"Gr\xfc\xdfe\" NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+			a1 OK FETCH completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let fetches = client.fetch(1, "(BODY[HEADER] ENVELOPE)").unwrap();
+		assert!(fetches[0].header() == Some(&b"Subject: Gr\xfc\xdfe\r\n\r\n"[..]), "Header should be kept as bytes");
+		let subject = fetches[0].envelope.as_ref().and_then(|envelope| envelope.subject.clone());
+		assert!(subject == Some(String::from("Gr\u{fffd}\u{fffd}e")), "Subject should be decoded lossily");
+	}
+
+	#[test]
+	fn readline_bare_cr() {
+		let mock_stream = MockStream::new(b"* OK a\rb\nc\r\n".to_vec());
+		let mut client = Client::new(mock_stream);
+		assert!(client.read_response_line().unwrap() == b"* OK a\rb\nc\r\n".to_vec(), "Only CRLF should end a line");
+	}
+
+	#[test]
+	fn readline_eof() {
+		let mock_stream = MockStream::new(b"* OK incomplete".to_vec());
+		let mut client = Client::new(mock_stream);
+		assert!(client.read_response_line().is_err(), "Incomplete line should fail");
+	}
+
+	#[test]
+	fn read_response_after_parse_error() {
+		let response = b"* 2 FETCH (UID \"broken\r\n\
+			* 3 FETCH (UID 3)\r\n\
+			a1 OK FETCH completed\r\n\
+			a1 OK Late completion\r\n\
+			* 5 FETCH (UID 5)\r\n\
+			a2 OK FETCH completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		assert!(client.fetch(2..4, "UID").is_err(), "Broken response should fail");
+		let fetches = client.fetch(5, "UID").unwrap();
+		assert!(fetches.len() == 1 && fetches[0].message == 5, "Responses of the failed command should not be returned");
+	}
+
+	#[test]
+	fn status_text_braces() {
+		let response = b"* OK [ALERT] quota {3}\r\n\
+			a1 OK NOOP completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		client.noop().unwrap();
+		assert!(client.response_codes() == &[ResponseCode::Alert], "Braces in the status text should not start a literal");
+	}
+
 	#[test]
 	fn read_greeting() {
 		let greeting = "* OK Dovecot ready.\r\n";
@@ -333,13 +1117,24 @@ mod tests {
 		client.read_greeting().unwrap();
 	}
 
+	#[test]
+	fn read_greeting_bye() {
+		let greeting = "* BYE [ALERT] Too many connections\r\n";
+		let mock_stream = MockStream::new(greeting.as_bytes().to_vec());
+		let mut client = Client::new(mock_stream);
+		match client.read_greeting() {
+			Err(Error::ByeResponse(Some(ResponseCode::Alert), ref text)) if text == "Too many connections" => {},
+			result => panic!("BYE greeting should be an error: {:?}", result)
+		}
+	}
+
 	#[test]
 	#[should_panic]
 	fn readline_err() {
 		// TODO Check the error test
 		let mock_stream = MockStream::new_err();
 		let mut client = Client::new(mock_stream);
-		client.readline().unwrap();
+		client.read_response_line().unwrap();
 	}
 
 	#[test]
@@ -366,7 +1161,57 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.login(username, password).unwrap();
-		assert!(client.stream.written_buf == command.as_bytes().to_vec(), "Invalid login command");
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid login command");
+	}
+
+	#[test]
+	fn login_quoted() {
+		let response = b"a1 OK Logged in\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		client.login("fred", "pass \"word\"\\").unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 LOGIN fred \"pass \\\"word\\\"\\\\\"\r\n".to_vec(), "Password should be quoted");
+	}
+
+	#[test]
+	fn login_literal() {
+		let response = b"+ Ready\r\n\
+			a1 NO Invalid credentials\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		assert!(client.login("fred", "secret\r\na2 DELETE INBOX").is_err(), "Login should fail");
+		assert!(client.stream.get_ref().written_buf == b"a1 LOGIN fred {23}\r\nsecret\r\na2 DELETE INBOX\r\n".to_vec(),
+			"Password with CRLF should be sent as literal");
+	}
+
+	#[test]
+	fn keyword_injection() {
+		let mock_stream = MockStream::new(Vec::new());
+		let mut client = Client::new(mock_stream);
+		let keyword = Flag::Keyword(String::from("x)\r\na2 DELETE INBOX\r\na3 NOOP ("));
+		match client.store(1, &StoreQuery::add(&[keyword.clone()])) {
+			Err(Error::InvalidArgument(_)) => {},
+			result => panic!("Unexpected store result {:?}", result)
+		}
+		assert!(client.append("INBOX", &[keyword], None, b"Hello").is_err(), "Append with invalid keyword should fail");
+		let query = SearchQuery::new().keyword("x)\r\na2 DELETE INBOX");
+		assert!(client.search(&query).is_err(), "Search with invalid keyword should fail");
+		assert!(client.stream.get_ref().written_buf.is_empty(), "Invalid keywords should not be sent");
+	}
+
+	#[test]
+	fn server_flags_rejected() {
+		let mock_stream = MockStream::new(Vec::new());
+		let mut client = Client::new(mock_stream);
+		match client.store(1, &StoreQuery::add(&[Flag::Recent])) {
+			Err(Error::InvalidArgument(_)) => {},
+			result => panic!("Unexpected store result {:?}", result)
+		}
+		match client.append("INBOX", &[Flag::Seen, Flag::MayCreate], None, b"Hello") {
+			Err(Error::InvalidArgument(_)) => {},
+			result => panic!("Unexpected append result {:?}", result)
+		}
+		assert!(client.stream.get_ref().written_buf.is_empty(), "\\Recent and \\* should not be sent");
 	}
 
 	#[test]
@@ -376,7 +1221,7 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.logout().unwrap();
-		assert!(client.stream.written_buf == command.as_bytes().to_vec(), "Invalid logout command");
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid logout command");
 	}
 
 	#[test]
@@ -388,7 +1233,7 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.rename(current_mailbox_name, new_mailbox_name).unwrap();
-		assert!(client.stream.written_buf == command.as_bytes().to_vec(), "Invalid rename command");
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid rename command");
 	}
 
 	#[test]
@@ -399,7 +1244,7 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.subscribe(mailbox).unwrap();
-		assert!(client.stream.written_buf == command.as_bytes().to_vec(), "Invalid subscribe command");
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid subscribe command");
 	}
 
 	#[test]
@@ -410,7 +1255,7 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.unsubscribe(mailbox).unwrap();
-		assert!(client.stream.written_buf == command.as_bytes().to_vec(), "Invalid unsubscribe command");
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid unsubscribe command");
 	}
 
 	#[test]
@@ -419,7 +1264,7 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.expunge().unwrap();
-		assert!(client.stream.written_buf == b"a1 EXPUNGE\r\n".to_vec(), "Invalid expunge command");
+		assert!(client.stream.get_ref().written_buf == b"a1 EXPUNGE\r\n".to_vec(), "Invalid expunge command");
 	}
 
 	#[test]
@@ -428,7 +1273,7 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.check().unwrap();
-		assert!(client.stream.written_buf == b"a1 CHECK\r\n".to_vec(), "Invalid check command");
+		assert!(client.stream.get_ref().written_buf == b"a1 CHECK\r\n".to_vec(), "Invalid check command");
 	}
 
 	#[test]
@@ -442,21 +1287,23 @@ mod tests {
 			* OK [UIDNEXT 2] Predicted next UID\r\n\
 			a1 OK [READ-ONLY] Select completed.\r\n".to_vec();
 		let expected_mailbox = Mailbox {
-			flags: String::from("(\\Answered \\Flagged \\Deleted \\Seen \\Draft)"),
+			flags: vec![Flag::Answered, Flag::Flagged, Flag::Deleted, Flag::Seen, Flag::Draft],
 			exists: 1,
 			recent: 1,
 			unseen: Some(1),
-			permanent_flags: Some(String::from("()")),
+			permanent_flags: Some(vec![]),
 			uid_next: Some(2),
-			uid_validity: Some(1257842737)
+			uid_validity: Some(1257842737),
+			highest_mod_seq: None
 		};
 		let mailbox_name = "INBOX";
 		let command = format!("a1 EXAMINE {}\r\n", mailbox_name);
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		let mailbox = client.examine(mailbox_name).unwrap();
-		assert!(client.stream.written_buf == command.as_bytes().to_vec(), "Invalid examine command");
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid examine command");
 		assert!(mailbox == expected_mailbox, "Unexpected mailbox returned");
+		assert!(client.response_codes().contains(&ResponseCode::ReadOnly), "Missing READ-ONLY response code");
 	}
 
 	#[test]
@@ -470,20 +1317,21 @@ mod tests {
 			* OK [UIDNEXT 2] Predicted next UID\r\n\
 			a1 OK [READ-ONLY] Select completed.\r\n".to_vec();
 		let expected_mailbox = Mailbox {
-			flags: String::from("(\\Answered \\Flagged \\Deleted \\Seen \\Draft)"),
+			flags: vec![Flag::Answered, Flag::Flagged, Flag::Deleted, Flag::Seen, Flag::Draft],
 			exists: 1,
 			recent: 1,
 			unseen: Some(1),
-			permanent_flags: Some(String::from("(\\* \\Answered \\Flagged \\Deleted \\Draft \\Seen)")),
+			permanent_flags: Some(vec![Flag::MayCreate, Flag::Answered, Flag::Flagged, Flag::Deleted, Flag::Draft, Flag::Seen]),
 			uid_next: Some(2),
-			uid_validity: Some(1257842737)
+			uid_validity: Some(1257842737),
+			highest_mod_seq: None
 		};
 		let mailbox_name = "INBOX";
 		let command = format!("a1 SELECT {}\r\n", mailbox_name);
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		let mailbox = client.select(mailbox_name).unwrap();
-		assert!(client.stream.written_buf == command.as_bytes().to_vec(), "Invalid select command");
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid select command");
 		assert!(mailbox == expected_mailbox, "Unexpected mailbox returned");
 	}
 
@@ -495,8 +1343,144 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		let capabilities = client.capability().unwrap();
-		assert!(client.stream.written_buf == b"a1 CAPABILITY\r\n".to_vec(), "Invalid capability command");
-		assert!(capabilities == expected_capabilities, "Unexpected capabilities response");
+		assert!(client.stream.get_ref().written_buf == b"a1 CAPABILITY\r\n".to_vec(), "Invalid capability command");
+		assert!(capabilities.iter().collect::<Vec<_>>() == expected_capabilities, "Unexpected capabilities response");
+		assert!(capabilities.auth_mechanisms() == vec!["GSSAPI"], "Unexpected auth mechanisms");
+
+		// The second call is answered from the cache.
+		client.capability().unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 CAPABILITY\r\n".to_vec(), "Capabilities should be cached");
+	}
+
+	#[test]
+	fn capabilities_from_greeting_and_login() {
+		let response = b"* OK [CAPABILITY IMAP4rev1 LOGINDISABLED STARTTLS] Dovecot ready.\r\n\
+			a1 OK [CAPABILITY IMAP4rev1 IDLE MOVE] Logged in\r\n\
+			a2 OK Logged in\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		client.read_greeting().unwrap();
+		assert!(client.has_capability("STARTTLS").unwrap(), "Capabilities from the greeting should be cached");
+		assert!(!client.has_capability("IDLE").unwrap(), "Unexpected capability");
+
+		client.login("username", "password").unwrap();
+		assert!(client.has_capability("IDLE").unwrap(), "Capabilities after LOGIN should be cached");
+		assert!(!client.has_capability("STARTTLS").unwrap(), "Capabilities should be replaced after LOGIN");
+
+		client.login("username", "password").unwrap();
+		assert!(client.capabilities.is_none(), "Capabilities should be invalidated after LOGIN");
+	}
+
+	#[test]
+	fn copy_trycreate() {
+		let response = b"a1 NO [TRYCREATE] Mailbox doesn't exist: Archive\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		match client.copy(1..4, "Archive") {
+			Err(Error::NoResponse(Some(ResponseCode::TryCreate), _)) => {},
+			result => panic!("Unexpected copy result {:?}", result)
+		}
+	}
+
+	#[test]
+	fn uid_copy_copyuid() {
+		let response = b"a1 OK [COPYUID 38505 304,319:320 3958:3956] Done\r\n\
+			a2 OK COPY completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let copy_uid = client.uid_copy(vec![304, 319, 320], "Archive").unwrap();
+		assert!(copy_uid == Some((38505, vec![(304, 3956), (319, 3957), (320, 3958)])),
+			"Reversed ranges should be mapped in ascending order, got {:?}", copy_uid);
+		assert!(client.copy(1, "Archive").unwrap() == None, "COPYUID should not be kept between commands");
+	}
+
+	#[test]
+	fn uid_expunge() {
+		let response = b"* 3 EXPUNGE\r\n\
+			a1 OK UID EXPUNGE completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		client.uid_expunge(3000..3003).unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 UID EXPUNGE 3000:3002\r\n".to_vec(), "Invalid uid expunge command");
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Expunge(3)), "Expected EXPUNGE");
+	}
+
+	#[test]
+	fn empty_sequence_set() {
+		let mock_stream = MockStream::new(Vec::new());
+		let mut client = Client::new(mock_stream);
+		assert!(client.fetch(SequenceSet::new(), "UID").unwrap().is_empty(), "Unexpected fetch results");
+		assert!(client.uid_store(Vec::new(), &StoreQuery::add(&[Flag::Seen])).unwrap().is_empty(), "Unexpected store results");
+		assert!(client.copy(SequenceSet::new(), "Archive").unwrap() == None, "Unexpected COPYUID");
+		assert!(client.mv(SequenceSet::new(), "Archive").unwrap(), "Empty move should be complete");
+		client.uid_expunge(SequenceSet::new()).unwrap();
+		assert!(client.stream.get_ref().written_buf.is_empty(), "No commands should be sent for empty sets");
+	}
+
+	#[test]
+	fn unsolicited_responses_on_failure() {
+		let response = b"* 5 EXISTS\r\n\
+			a1 NO [TRYCREATE] Mailbox doesn't exist: Archive\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		assert!(client.copy(1, "Archive").is_err(), "COPY should fail");
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Exists(5)),
+			"Mailbox updates should be delivered when a command fails");
+	}
+
+	#[test]
+	fn unsolicited_responses() {
+		let response = b"* 5 EXISTS\r\n\
+			* 3 EXPUNGE\r\n\
+			* 1 RECENT\r\n\
+			* 4 FETCH (FLAGS (\\Seen))\r\n\
+			a1 OK NOOP completed\r\n\
+			* 2 FETCH (UID 7)\r\n\
+			* 6 EXISTS\r\n\
+			a2 OK FETCH completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		client.noop().unwrap();
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Exists(5)), "Expected EXISTS");
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Expunge(3)), "Expected EXPUNGE");
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Recent(1)), "Expected RECENT");
+		match client.unsolicited_responses.try_recv() {
+			Ok(UnsolicitedResponse::Fetch(ref fetch)) => {
+				assert!(fetch.message == 4 && fetch.flags == vec![Flag::Seen], "Unexpected FETCH");
+			},
+			response => panic!("Expected FETCH, got {:?}", response)
+		}
+		assert!(client.unsolicited_responses.try_recv().is_err(), "Unexpected unsolicited response");
+
+		let fetches = client.fetch(2, "UID").unwrap();
+		assert!(fetches.len() == 1 && fetches[0].uid == Some(7), "Solicited FETCH should be returned");
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Exists(6)), "Expected EXISTS");
+	}
+
+	#[test]
+	fn append() {
+		let response = b"+ Ready for literal data\r\n\
+			a1 OK [APPENDUID 38505 3955] APPEND completed\r\n".to_vec();
+		let message = b"From: Fred Foobar <foobar@Blurdybloop.COM>\r\nSubject: afternoon meeting\r\n\r\nHello Joe\r\n";
+		let command = format!("a1 APPEND saved-messages (\\Seen \\Draft) \"17-Jul-1996 02:44:25 -0700\" {{{}}}\r\n{}\r\n",
+			message.len(), String::from_utf8_lossy(message));
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let uid = client.append("saved-messages", &[Flag::Seen, Flag::Draft], Some("17-Jul-1996 02:44:25 -0700"), message).unwrap();
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid append command");
+		assert!(uid == Some((38505, 3955)), "Unexpected APPENDUID");
+	}
+
+	#[test]
+	fn append_rejected() {
+		let response = b"a1 NO [TRYCREATE] Mailbox doesn't exist\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		match client.append("Drafts", &[], None, b"Subject: test\r\n\r\n") {
+			Err(Error::NoResponse(Some(ResponseCode::TryCreate), _)) => {},
+			result => panic!("Unexpected append result {:?}", result)
+		}
+		assert!(client.stream.get_ref().written_buf == b"a1 APPEND Drafts {17}\r\n".to_vec(), "Literal should not be sent");
 	}
 
 	#[test]
@@ -507,7 +1491,7 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.create(mailbox_name).unwrap();
-		assert!(client.stream.written_buf == command.as_bytes().to_vec(), "Invalid create command");
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid create command");
 	}
 
 	#[test]
@@ -518,7 +1502,7 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.delete(mailbox_name).unwrap();
-		assert!(client.stream.written_buf == command.as_bytes().to_vec(), "Invalid delete command");
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid delete command");
 	}
 
 	#[test]
@@ -527,7 +1511,7 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.noop().unwrap();
-		assert!(client.stream.written_buf == b"a1 NOOP\r\n".to_vec(), "Invalid noop command");
+		assert!(client.stream.get_ref().written_buf == b"a1 NOOP\r\n".to_vec(), "Invalid noop command");
 	}
 
 	#[test]
@@ -536,21 +1520,113 @@ mod tests {
 		let mock_stream = MockStream::new(response);
 		let mut client = Client::new(mock_stream);
 		client.close().unwrap();
-		assert!(client.stream.written_buf == b"a1 CLOSE\r\n".to_vec(), "Invalid close command");
+		assert!(client.stream.get_ref().written_buf == b"a1 CLOSE\r\n".to_vec(), "Invalid close command");
 	}
 
 	#[test]
 	fn store() {
-		generic_store(" ", |mut c, set, query| c.store(set, query));
+		generic_store(" ", |mut c, set, _| c.store(set, &StoreQuery::add(&[Flag::Deleted])));
 	}
 
 	#[test]
 	fn uid_store() {
-		generic_store(" UID ", |mut c, set, query| c.uid_store(set, query));
+		generic_store(" UID ", |mut c, set, _| c.uid_store(set, &StoreQuery::add(&[Flag::Deleted])));
+	}
+
+	#[test]
+	fn store_flags() {
+		let response = b"* 2 FETCH (FLAGS (\\Deleted \\Seen))\r\n\
+			* 3 FETCH (FLAGS (\\Deleted $Forwarded))\r\n\
+			a1 OK STORE completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let fetches = client.store(2..4, &StoreQuery::add(&[Flag::Deleted])).unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 STORE 2:3 +FLAGS (\\Deleted)\r\n".to_vec(), "Invalid store command");
+		assert!(fetches.len() == 2, "Unexpected number of fetch results");
+		assert!(fetches[0].message == 2 && fetches[0].flags == vec![Flag::Deleted, Flag::Seen], "Unexpected flags");
+		assert!(fetches[1].flags == vec![Flag::Deleted, Flag::Keyword(String::from("$Forwarded"))], "Unexpected flags");
 	}
 
-	fn generic_store<F, T, E>(prefix: &str, op: F)
-		where F: FnOnce(&mut Client<MockStream>, &str, &str) -> Result<T, E> {
+	#[test]
+	fn store_unchanged_since() {
+		let response = b"* 5 FETCH (UID 105 MODSEQ (320162339))\r\n\
+			a1 OK [MODIFIED 7,9] Conditional STORE failed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let fetches = client.store(5..10, &StoreQuery::add(&[Flag::Seen]).silent().unchanged_since(320162338)).unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 STORE 5:9 (UNCHANGEDSINCE 320162338) +FLAGS.SILENT (\\Seen)\r\n".to_vec(),
+			"Invalid store command");
+		assert!(fetches.len() == 1 && fetches[0].mod_seq == Some(320162339), "Unexpected fetch results");
+		assert!(client.response_codes() == &[ResponseCode::Modified("7,9".parse().unwrap())], "Missing MODIFIED response code");
+	}
+
+	#[test]
+	fn fetch_changed_since() {
+		let response = b"* 1 FETCH (UID 4 MODSEQ (65402) FLAGS (\\Seen))\r\n\
+			a1 OK FETCH completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let fetches = client.uid_fetch_changed_since(SequenceSet::all(), "(UID FLAGS)", 12345).unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 UID FETCH 1:* (UID FLAGS) (CHANGEDSINCE 12345)\r\n".to_vec(),
+			"Invalid fetch command");
+		assert!(fetches.len() == 1 && fetches[0].mod_seq == Some(65402), "Unexpected fetch results");
+	}
+
+	#[test]
+	fn select_qresync() {
+		let response = b"* ENABLED QRESYNC\r\n\
+			a1 OK Enabled\r\n\
+			* 314 EXISTS\r\n\
+			* OK [UIDVALIDITY 67890007] UIDVALIDITY\r\n\
+			* OK [HIGHESTMODSEQ 90060115205545359] Highest mailbox modsequence\r\n\
+			* VANISHED (EARLIER) 41,43:116,118,120:211,214:540\r\n\
+			* 49 FETCH (UID 117 FLAGS (\\Seen \\Answered) MODSEQ (90060115194045001))\r\n\
+			a2 OK [READ-WRITE] mailbox selected\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let known_uids: SequenceSet = "41:211,214:541".parse().unwrap();
+		let (mailbox, vanished, changed) = client.select_qresync("INBOX", 67890007, 90060128194045007, Some(&known_uids)).unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 ENABLE QRESYNC\r\n\
+			a2 SELECT INBOX (QRESYNC (67890007 90060128194045007 41:211,214:541))\r\n".to_vec(), "Invalid select command");
+		assert!(mailbox.exists == 314 && mailbox.highest_mod_seq == Some(90060115205545359), "Unexpected mailbox {}", mailbox);
+		assert!(vanished.to_string() == "41,43:116,118,120:211,214:540", "Unexpected vanished UIDs {}", vanished);
+		assert!(changed.len() == 1 && changed[0].uid == Some(117), "Unexpected changed messages");
+	}
+
+	#[test]
+	fn select_qresync_no_known_uids() {
+		let response = b"* ENABLED QRESYNC\r\n\
+			a1 OK Enabled\r\n\
+			* 0 EXISTS\r\n\
+			a2 OK [READ-WRITE] mailbox selected\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		client.select_qresync("INBOX", 67890007, 90060128194045007, Some(&SequenceSet::new())).unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 ENABLE QRESYNC\r\n\
+			a2 SELECT INBOX (QRESYNC (67890007 90060128194045007))\r\n".to_vec(), "Empty known UIDs should be omitted");
+	}
+
+	#[test]
+	fn enable() {
+		let response = b"* ENABLED UTF8=ACCEPT\r\n\
+			a1 OK Enabled\r\n\
+			* ENABLED QRESYNC\r\n\
+			a2 OK Enabled\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let enabled = client.enable(&["UTF8=ACCEPT", "CONDSTORE"]).unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 ENABLE UTF8=ACCEPT CONDSTORE\r\n".to_vec(), "Invalid enable command");
+		assert!(enabled.has("UTF8=ACCEPT") && !enabled.has("CONDSTORE"), "Unexpected enabled extensions {:?}", enabled);
+		assert!(client.utf8_mailbox_names, "Mailbox names should be UTF-8 once UTF8=ACCEPT is enabled");
+		assert!(!client.is_enabled("CONDSTORE"), "CONDSTORE should not be enabled");
+
+		client.enable(&["QRESYNC"]).unwrap();
+		assert!(client.is_enabled("utf8=accept") && client.is_enabled("QRESYNC"), "Enabled extensions should accumulate");
+		assert!(client.is_enabled("CONDSTORE"), "QRESYNC should imply CONDSTORE");
+	}
+
+	fn generic_store<F, T>(prefix: &str, op: F)
+		where F: FnOnce(&mut Client<MockStream>, &SequenceSet, &str) -> Result<T> {
 
 		let res = "* 2 FETCH (FLAGS (\\Deleted \\Seen))\r\n\
 			* 3 FETCH (FLAGS (\\Deleted))\r\n\
@@ -560,7 +1636,7 @@ mod tests {
 		generic_with_uid(
 			res,
 			"STORE",
-			"2.4",
+			"2:4",
 			"+FLAGS (\\Deleted)",
 			prefix,
 			op,
@@ -577,8 +1653,8 @@ mod tests {
 		generic_copy(" UID ", |mut c, set, query| c.uid_copy(set, query))
 	}
 
-	fn generic_copy<F, T, E>(prefix: &str, op: F)
-		where F: FnOnce(&mut Client<MockStream>, &str, &str) -> Result<T, E> {
+	fn generic_copy<F, T>(prefix: &str, op: F)
+		where F: FnOnce(&mut Client<MockStream>, &SequenceSet, &str) -> Result<T> {
 
 		generic_with_uid(
 			"OK COPY completed\r\n",
@@ -600,8 +1676,8 @@ mod tests {
 		generic_fetch(" UID ", |mut c, seq, query| c.uid_fetch(seq, query))
 	}
 
-	fn generic_fetch<F, T, E>(prefix: &str, op: F)
-		where F: FnOnce(&mut Client<MockStream>, &str, &str) -> Result<T, E> {
+	fn generic_fetch<F, T>(prefix: &str, op: F)
+		where F: FnOnce(&mut Client<MockStream>, &SequenceSet, &str) -> Result<T> {
 
 		generic_with_uid(
 			"OK FETCH completed\r\n",
@@ -613,19 +1689,282 @@ mod tests {
 		);
 	}
 
-	fn generic_with_uid<F, T, E>(
+	#[test]
+	fn utf7_mailbox_names() {
+		let response = b"* LIST () \"/\" \"Entw&APw-rfe\"\r\n\
+			a1 OK LIST completed\r\n\
+			+ Ready\r\n\
+			a2 OK CREATE completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let names = client.list("", "Entwü*").unwrap();
+		assert!(names.len() == 1 && names[0].name == "Entwürfe", "Name should be decoded");
+		client.utf8_mailbox_names = true;
+		client.create("Entwürfe").unwrap();
+		assert!(client.stream.get_ref().written_buf == "a1 LIST \"\" Entw&APw-*\r\na2 CREATE {9}\r\nEntwürfe\r\n".as_bytes().to_vec(),
+			"Invalid commands");
+	}
+
+	#[test]
+	fn mv() {
+		let response = b"* OK [COPYUID 432432 42:43 11:12] Moved\r\n\
+			* 2 EXPUNGE\r\n\
+			* 2 EXPUNGE\r\n\
+			a1 OK MOVE completed\r\n";
+		let mut client = client_with_capabilities(response, None, &["IMAP4rev1", "MOVE"]);
+		assert!(client.mv(2..4, "Archive").unwrap(), "Messages should be removed");
+		assert!(client.stream.get_ref().written_buf == b"a1 MOVE 2:3 Archive\r\n".to_vec(), "Invalid move command");
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Expunge(2)), "Expected EXPUNGE");
+	}
+
+	#[test]
+	fn mv_fallback() {
+		let response = b"* SEARCH 42 43\r\n\
+			a1 OK SEARCH completed\r\n\
+			a2 OK COPY completed\r\n\
+			a3 OK STORE completed\r\n\
+			* 2 EXPUNGE\r\n\
+			* 2 EXPUNGE\r\n\
+			a4 OK UID EXPUNGE completed\r\n";
+		let mut client = client_with_capabilities(response, None, &["IMAP4rev1", "UIDPLUS"]);
+		assert!(client.mv(2..4, "Archive").unwrap(), "Messages should be expunged");
+		assert!(client.stream.get_ref().written_buf == b"a1 UID SEARCH 2:3\r\n\
+			a2 UID COPY 42:43 Archive\r\n\
+			a3 UID STORE 42:43 +FLAGS.SILENT (\\Deleted)\r\n\
+			a4 UID EXPUNGE 42:43\r\n".to_vec(), "Invalid move commands");
+	}
+
+	#[test]
+	fn mv_fallback_unrelated_fetch() {
+		let response = b"* 7 FETCH (UID 99 FLAGS (\\Seen))\r\n\
+			* SEARCH 42\r\n\
+			a1 OK SEARCH completed\r\n\
+			a2 OK COPY completed\r\n\
+			a3 OK STORE completed\r\n\
+			a4 OK UID EXPUNGE completed\r\n";
+		let mut client = client_with_capabilities(response, None, &["IMAP4rev1", "UIDPLUS"]);
+		assert!(client.mv(2, "Archive").unwrap(), "Message should be expunged");
+		assert!(client.stream.get_ref().written_buf == b"a1 UID SEARCH 2\r\n\
+			a2 UID COPY 42 Archive\r\n\
+			a3 UID STORE 42 +FLAGS.SILENT (\\Deleted)\r\n\
+			a4 UID EXPUNGE 42\r\n".to_vec(), "Only the requested message should be moved");
+		match client.unsolicited_responses.try_recv() {
+			Ok(UnsolicitedResponse::Fetch(ref fetch)) => assert!(fetch.uid == Some(99), "Unexpected FETCH"),
+			response => panic!("Expected FETCH, got {:?}", response)
+		}
+	}
+
+	#[test]
+	fn uid_mv_without_uidplus() {
+		let response = b"a1 OK COPY completed\r\n\
+			a2 OK STORE completed\r\n";
+		let mut client = client_with_capabilities(response, None, &["IMAP4rev1"]);
+		assert!(!client.uid_mv(42, "Archive").unwrap(), "Move without UIDPLUS should be incomplete");
+		assert!(client.stream.get_ref().written_buf == b"a1 UID COPY 42 Archive\r\n\
+			a2 UID STORE 42 +FLAGS.SILENT (\\Deleted)\r\n".to_vec(), "Messages should not be expunged without UIDPLUS");
+	}
+
+	fn client_with_capabilities(response: &[u8], timeout_pos: Option<usize>, capabilities: &[&str]) -> Client<MockStream> {
+		let mut mock_stream = MockStream::new(response.to_vec());
+		if let Some(pos) = timeout_pos {
+			mock_stream = mock_stream.timeout_at(pos);
+		}
+		let mut client = Client::new(mock_stream);
+		client.capabilities = Some(Capabilities::new(capabilities.iter().map(|c| c.to_string()).collect()));
+		client
+	}
+
+	#[test]
+	fn idle() {
+		let response = b"+ idling\r\n\
+			* 5 EXISTS\r\n\
+			a1 OK IDLE terminated\r\n";
+		let mut client = client_with_capabilities(response, None, &["IMAP4rev1", "IDLE"]);
+		assert!(client.idle().unwrap().wait_timeout(Duration::from_secs(60)).unwrap(), "Update should end IDLE");
+		assert!(client.stream.get_ref().written_buf == b"a1 IDLE\r\nDONE\r\n".to_vec(), "Invalid idle commands");
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Exists(5)), "Expected EXISTS");
+	}
+
+	#[test]
+	fn idle_keepalive() {
+		let response = b"+ idling\r\n\
+			a1 OK IDLE terminated\r\n\
+			+ idling\r\n\
+			* 3 EXPUNGE\r\n\
+			a2 OK IDLE terminated\r\n";
+		let mut client = client_with_capabilities(response, Some(10), &["IMAP4rev1", "IDLE"]);
+		{
+			let mut idle = client.idle().unwrap();
+			idle.set_keepalive(Duration::from_millis(1));
+			idle.wait().unwrap();
+		}
+		assert!(client.stream.get_ref().written_buf == b"a1 IDLE\r\nDONE\r\na2 IDLE\r\nDONE\r\n".to_vec(),
+			"IDLE should be re-issued after a timeout");
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Expunge(3)), "Expected EXPUNGE");
+	}
+
+	#[test]
+	fn idle_timeout() {
+		let response = b"+ idling\r\n\
+			a1 OK IDLE terminated\r\n";
+		let mut client = client_with_capabilities(response, Some(10), &["IMAP4rev1", "IDLE"]);
+		assert!(!client.idle().unwrap().wait_timeout(Duration::from_millis(10)).unwrap(), "Wait should time out");
+		assert!(client.stream.get_ref().written_buf == b"a1 IDLE\r\nDONE\r\n".to_vec(), "Invalid idle commands");
+	}
+
+	#[test]
+	fn idle_timeout_mid_line() {
+		let response = b"+ idling\r\n\
+			* 5 EXISTS\r\n\
+			a1 OK IDLE terminated\r\n";
+		let mut client = client_with_capabilities(response, Some(15), &["IMAP4rev1", "IDLE"]);
+		assert!(client.idle().unwrap().wait_timeout(Duration::from_millis(10)).unwrap(), "Partly read update should not be lost");
+		assert!(client.stream.get_ref().written_buf == b"a1 IDLE\r\nDONE\r\n".to_vec(), "Invalid idle commands");
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Exists(5)), "Expected EXISTS");
+	}
+
+	#[test]
+	fn idle_error_sends_done() {
+		let response = b"+ idling\r\n\
+			* 5 FETCH (UID)\r\n\
+			a1 OK IDLE terminated\r\n\
+			a2 OK NOOP completed\r\n";
+		let mut client = client_with_capabilities(response, None, &["IMAP4rev1", "IDLE"]);
+		assert!(client.idle().unwrap().wait().is_err(), "Broken FETCH should fail");
+		client.noop().unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 IDLE\r\nDONE\r\na2 NOOP\r\n".to_vec(),
+			"IDLE should be ended after an error");
+	}
+
+	#[test]
+	fn idle_noop_fallback() {
+		let response = b"* 2 EXISTS\r\n\
+			a1 OK NOOP completed\r\n";
+		let mut client = client_with_capabilities(response, None, &["IMAP4rev1"]);
+		{
+			let mut idle = client.idle().unwrap();
+			idle.set_poll_interval(Duration::from_millis(1));
+			idle.wait().unwrap();
+		}
+		assert!(client.stream.get_ref().written_buf == b"a1 NOOP\r\n".to_vec(), "NOOP should be used without IDLE");
+		assert!(client.unsolicited_responses.try_recv() == Ok(UnsolicitedResponse::Exists(2)), "Expected EXISTS");
+	}
+
+	#[test]
+	fn list() {
+		let response = b"* LIST (\\HasNoChildren) \".\" \"INBOX\"\r\n\
+			* LIST (\\Noselect \\HasChildren) NIL \"Public Folders\"\r\n\
+			a1 OK LIST completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let names = client.list("", "*").unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 LIST \"\" *\r\n".to_vec(), "Invalid list command");
+		assert!(names.len() == 2, "Unexpected number of names");
+		assert!(names[0].name == "INBOX" && names[0].delimiter == Some(String::from(".")), "Unexpected name");
+		assert!(names[0].attributes == vec![NameAttribute::HasNoChildren], "Unexpected attributes");
+		assert!(names[1].name == "Public Folders" && names[1].delimiter == None, "Unexpected name");
+		assert!(!names[1].is_selectable(), "Noselect name should not be selectable");
+	}
+
+	#[test]
+	fn namespace() {
+		let response = b"* NAMESPACE ((\"INBOX.\" \".\")) ((\"Other Users.\" \".\")) ((\"&AMk-quipe.\" \".\"))\r\n\
+			a1 OK NAMESPACE command completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let namespaces = client.namespace().unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 NAMESPACE\r\n".to_vec(), "Invalid namespace command");
+		assert!(namespaces.personal.len() == 1 && namespaces.personal[0].prefix == "INBOX.", "Unexpected personal namespaces");
+		assert!(namespaces.personal[0].delimiter == Some(String::from(".")), "Unexpected delimiter");
+		assert!(namespaces.other_users[0].prefix == "Other Users.", "Unexpected other users' namespaces");
+		assert!(namespaces.shared[0].prefix == "\u{c9}quipe.", "Shared prefix should be decoded from modified UTF-7");
+	}
+
+	#[test]
+	fn id() {
+		let response = b"* ID (\"name\" \"Dovecot\")\r\n\
+			a1 OK ID completed\r\n\
+			* ID NIL\r\n\
+			a2 OK ID completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let mut fields = HashMap::new();
+		fields.insert(String::from("version"), String::from("0.7"));
+		fields.insert(String::from("name"), String::from("rust-imap"));
+		let id = client.id(Some(fields)).unwrap();
+		assert!(id.get("name") == Some(&String::from("Dovecot")), "Unexpected server ID {:?}", id);
+		assert!(client.id(None).unwrap().is_empty(), "Unexpected server ID");
+		assert!(client.stream.get_ref().written_buf == b"a1 ID (\"name\" \"rust-imap\" \"version\" \"0.7\")\r\n\
+			a2 ID NIL\r\n".to_vec(), "Invalid id commands");
+	}
+
+	#[test]
+	fn status() {
+		let response = b"* STATUS blurdybloop (MESSAGES 231 UIDNEXT 44292 HIGHESTMODSEQ 7011231777)\r\n\
+			a1 OK STATUS completed\r\n".to_vec();
+		let expected_status = MailboxStatus {
+			messages: Some(231),
+			uid_next: Some(44292),
+			highest_mod_seq: Some(7011231777),
+			..MailboxStatus::default()
+		};
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let status = client.status("blurdybloop", &[StatusItem::Messages, StatusItem::UidNext, StatusItem::HighestModSeq]).unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 STATUS blurdybloop (MESSAGES UIDNEXT HIGHESTMODSEQ)\r\n".to_vec(), "Invalid status command");
+		assert!(status == expected_status, "Unexpected status returned");
+	}
+
+	#[test]
+	fn search() {
+		generic_search(" ", |c, query| c.search(query))
+	}
+
+	#[test]
+	fn uid_search() {
+		generic_search(" UID ", |c, query| c.uid_search(query))
+	}
+
+	fn generic_search<F>(prefix: &str, op: F)
+		where F: FnOnce(&mut Client<MockStream>, &SearchQuery) -> Result<HashSet<u32>> {
+
+		let response = b"* SEARCH 2 84 882\r\n\
+			a1 OK SEARCH completed\r\n".to_vec();
+		let query = SearchQuery::new().flagged().since(Date::new(1994, 2, 1).unwrap()).not(SearchQuery::new().from("Smith"));
+		let command = format!("a1{}SEARCH FLAGGED SINCE 1-Feb-1994 NOT FROM \"Smith\"\r\n", prefix);
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let results = op(&mut client, &query).unwrap();
+		assert!(client.stream.get_ref().written_buf == command.as_bytes().to_vec(), "Invalid search command");
+		assert!(results == [2, 84, 882].iter().cloned().collect(), "Unexpected search results");
+	}
+
+	#[test]
+	fn fetch_body() {
+		let response = b"* 2 FETCH (UID 7 BODY[TEXT] {13}\r\nHello\r\nWorld!)\r\n\
+			a1 OK FETCH completed\r\n".to_vec();
+		let mock_stream = MockStream::new(response);
+		let mut client = Client::new(mock_stream);
+		let fetches = client.fetch(2, "(UID BODY[TEXT])").unwrap();
+		assert!(client.stream.get_ref().written_buf == b"a1 FETCH 2 (UID BODY[TEXT])\r\n".to_vec(), "Invalid fetch command");
+		assert!(fetches.len() == 1, "Unexpected number of fetch results");
+		assert!(fetches[0].message == 2 && fetches[0].uid == Some(7), "Unexpected fetch result");
+		assert!(fetches[0].text() == Some(&b"Hello\r\nWorld!"[..]), "Unexpected message text");
+	}
+
+	fn generic_with_uid<F, T>(
 		res: &str,
 		cmd: &str,
 		seq: &str,
 		query: &str,
 		prefix: &str,
-		op: F) where F: FnOnce(&mut Client<MockStream>, &str, &str) -> Result<T, E>,
+		op: F) where F: FnOnce(&mut Client<MockStream>, &SequenceSet, &str) -> Result<T>,
 	{
 
 		let resp = format!("a1 {}\r\n", res).as_bytes().to_vec();
 		let line = format!("a1{}{} {} {}\r\n", prefix, cmd, seq, query);
 		let mut client = Client::new(MockStream::new(resp));
-		let _ = op(&mut client, seq, query);
-		assert!(client.stream.written_buf == line.as_bytes().to_vec(), "Invalid command");
+		let _ = op(&mut client, &seq.parse().unwrap(), query);
+		assert!(client.stream.get_ref().written_buf == line.as_bytes().to_vec(), "Invalid command");
 	}
 }
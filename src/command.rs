@@ -0,0 +1,190 @@
+use std::fmt;
+use std::mem;
+
+/// A command line with its arguments encoded for sending. String arguments are sent as atoms
+/// where possible, as quoted strings otherwise, and as literals if they contain CR, LF or
+/// non-ASCII characters, so that they can never end the command line.
+///
+/// ```
+/// # use imap::command::Command;
+/// let command = Command::new("LOGIN").string("fred").string("pass word");
+/// assert_eq!(command.to_string(), "LOGIN fred \"pass word\"");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Command {
+    /// The lines that announce a literal (`{N}`), each followed by the data of the literal.
+    pub literal_lines: Vec<(String, Vec<u8>)>,
+    /// The rest of the command after the last literal.
+    pub line: String
+}
+
+impl Command {
+    /// Starts a command with raw text, e.g. the command name.
+    pub fn new(text: &str) -> Command {
+        Command::default().raw(text)
+    }
+
+    /// Appends raw text, which is sent as is.
+    pub fn raw(mut self, text: &str) -> Command {
+        self.push_separator();
+        self.line.push_str(text);
+        self
+    }
+
+    /// Appends a string argument (astring).
+    pub fn string(self, value: &str) -> Command {
+        self.encoded(value.as_bytes(), is_astring_char)
+    }
+
+    /// Appends a string argument that is quoted even if it is an atom, e.g. a date-time.
+    pub fn quoted(self, value: &str) -> Command {
+        self.encoded(value.as_bytes(), |_| false)
+    }
+
+    /// Appends a mailbox name pattern of LIST or LSUB, which may contain the wildcards `*`
+    /// and `%` without being quoted.
+    pub fn list_mailbox(self, pattern: &str) -> Command {
+        self.encoded(pattern.as_bytes(), |b| is_astring_char(b) || b == b'%' || b == b'*')
+    }
+
+    /// Appends data that is always sent as a literal.
+    pub fn literal(mut self, data: &[u8]) -> Command {
+        self.push_separator();
+        self.push_literal(data);
+        self
+    }
+
+    /// Appends another command, e.g. the arguments of a search query.
+    pub fn command(mut self, command: Command) -> Command {
+        self.push_separator();
+        self.push_command(command);
+        self
+    }
+
+    /// Appends commands as a parenthesized list.
+    pub fn group(mut self, commands: Vec<Command>) -> Command {
+        self.push_separator();
+        self.line.push('(');
+        for (i, command) in commands.into_iter().enumerate() {
+            if i > 0 {
+                self.line.push(' ');
+            }
+            self.push_command(command);
+        }
+        self.line.push(')');
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.literal_lines.is_empty() && self.line.is_empty()
+    }
+
+    fn encoded<F: Fn(u8) -> bool>(mut self, value: &[u8], is_atom_char: F) -> Command {
+        self.push_separator();
+        if !value.is_empty() && value.iter().all(|&b| is_atom_char(b)) {
+            self.line.push_str(&String::from_utf8_lossy(value));
+        } else if value.iter().all(|&b| is_quoted_char(b)) {
+            self.line.push('"');
+            for &b in value.iter() {
+                if b == b'"' || b == b'\\' {
+                    self.line.push('\\');
+                }
+                self.line.push(b as char);
+            }
+            self.line.push('"');
+        } else {
+            self.push_literal(value);
+        }
+        self
+    }
+
+    fn push_separator(&mut self) {
+        if !self.is_empty() {
+            self.line.push(' ');
+        }
+    }
+
+    fn push_literal(&mut self, data: &[u8]) {
+        self.line.push_str(&format!("{{{}}}", data.len()));
+        let line = mem::replace(&mut self.line, String::new());
+        self.literal_lines.push((line, data.to_vec()));
+    }
+
+    fn push_command(&mut self, command: Command) {
+        for (line, data) in command.literal_lines.into_iter() {
+            self.line.push_str(&line);
+            let line = mem::replace(&mut self.line, String::new());
+            self.literal_lines.push((line, data));
+        }
+        self.line.push_str(&command.line);
+    }
+}
+
+/// Shows the command as sent, with the literals inline.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(ref line, ref data) in self.literal_lines.iter() {
+            try!(write!(f, "{}\r\n{}", line, String::from_utf8_lossy(data)));
+        }
+        f.write_str(&self.line)
+    }
+}
+
+/// Checks if the text can be sent as an atom, e.g. a flag keyword: a non-empty string of
+/// astring characters other than `]`.
+pub fn is_atom(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|b| b != b']' && is_astring_char(b))
+}
+
+/// Checks if the character can be part of an astring without quoting: any 7-bit character
+/// except controls and the atom-specials of RFC 3501, but including `]`.
+fn is_astring_char(b: u8) -> bool {
+    match b {
+        0...0x20 | 0x7f => false,
+        b'(' | b')' | b'{' | b'%' | b'*' | b'"' | b'\\' => false,
+        _ => b < 0x80
+    }
+}
+
+/// Checks if the character can be part of a quoted string, possibly escaped.
+fn is_quoted_char(b: u8) -> bool {
+    b > 0 && b < 0x80 && b != b'\r' && b != b'\n'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string() {
+        assert!(Command::new("SELECT").string("INBOX").to_string() == "SELECT INBOX", "Atom should not be quoted");
+        assert!(Command::new("SELECT").string("").to_string() == "SELECT \"\"", "Empty string should be quoted");
+        assert!(Command::new("SELECT").string("My \"Mail\\Box\"").to_string() == "SELECT \"My \\\"Mail\\\\Box\\\"\"",
+            "Quotes and backslashes should be escaped");
+        assert!(Command::new("SEARCH").raw("FROM").quoted("alice").to_string() == "SEARCH FROM \"alice\"", "String should be quoted");
+        assert!(Command::new("LIST").string("").list_mailbox("Arch%").to_string() == "LIST \"\" Arch%",
+            "Wildcards should not be quoted");
+    }
+
+    #[test]
+    fn atom() {
+        assert!(is_atom("$Forwarded"), "Keyword should be an atom");
+        assert!(!is_atom("") && !is_atom("a]") && !is_atom("a b") && !is_atom("x)\r\n"), "Invalid atoms should be rejected");
+    }
+
+    #[test]
+    fn literal() {
+        let command = Command::new("SELECT").string("INBOX\r\na2 DELETE INBOX");
+        assert!(command.literal_lines == vec![(String::from("SELECT {22}"), b"INBOX\r\na2 DELETE INBOX".to_vec())],
+            "CRLF should be sent as literal");
+        assert!(command.line == "", "Unexpected rest of command");
+        let command = Command::new("LOGIN").string("fred").string("pässword").raw("X");
+        assert!(command.to_string() == "LOGIN fred {9}\r\npässword X", "Unexpected command {}", command);
+    }
+
+    #[test]
+    fn group() {
+        let command = Command::new("SEARCH").raw("OR").group(vec![Command::new("TO").string("bob"), Command::new("SEEN")]);
+        assert!(command.to_string() == "SEARCH OR (TO bob SEEN)", "Unexpected command {}", command);
+    }
+}
@@ -0,0 +1,41 @@
+/// The envelope structure of a message, as returned by FETCH ENVELOPE.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Envelope {
+    pub date: Option<String>,
+    pub subject: Option<String>,
+    pub from: Vec<Address>,
+    pub sender: Vec<Address>,
+    pub reply_to: Vec<Address>,
+    pub to: Vec<Address>,
+    pub cc: Vec<Address>,
+    pub bcc: Vec<Address>,
+    pub in_reply_to: Option<String>,
+    pub message_id: Option<String>
+}
+
+/// An address in an envelope.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Address {
+    /// The display name, e.g. `John Doe`.
+    pub name: Option<String>,
+    /// The source route (at-domain-list), which is obsolete and almost always absent.
+    pub adl: Option<String>,
+    /// The local part, e.g. `john` in `john@example.com`.
+    pub mailbox: Option<String>,
+    /// The domain, e.g. `example.com` in `john@example.com`.
+    pub host: Option<String>,
+    /// The name of the group this address was listed in, if the header used RFC 2822 group
+    /// syntax such as `Team: john@example.com, jane@example.com;`.
+    pub group: Option<String>
+}
+
+impl Address {
+    /// Returns the address in `mailbox@host` form.
+    pub fn email(&self) -> Option<String> {
+        match (&self.mailbox, &self.host) {
+            (&Some(ref mailbox), &Some(ref host)) => Some(format!("{}@{}", mailbox, host)),
+            (&Some(ref mailbox), &None) => Some(mailbox.clone()),
+            _ => None
+        }
+    }
+}
@@ -5,6 +5,8 @@ use std::error::Error as StdError;
 
 use openssl::ssl::error::SslError;
 
+use super::response::ResponseCode;
+
 pub type Result<T> = result::Result<T, Error>;
 
 /// A set of errors that can occur in the IMAP client
@@ -14,10 +16,14 @@ pub enum Error {
     Io(IoError),
     /// An error from the `openssl` library.
     Ssl(SslError),
-    /// A BAD response from the IMAP server.
-    BadResponse(Vec<String>),
-    /// A NO response from the IMAP server.
-    NoResponse(Vec<String>),
+    /// A BAD response from the IMAP server, with its response code and human-readable text.
+    BadResponse(Option<ResponseCode>, String),
+    /// A NO response from the IMAP server, with its response code and human-readable text.
+    NoResponse(Option<ResponseCode>, String),
+    /// A BYE response from the IMAP server, with its response code and human-readable text.
+    ByeResponse(Option<ResponseCode>, String),
+    /// A command argument that cannot be sent safely, such as a keyword that is not an atom.
+    InvalidArgument(String),
     // Error parsing a server response.
     Parse(ParseError)
 }
@@ -50,8 +56,10 @@ impl StdError for Error {
             Error::Io(ref e) => e.description(),
             Error::Ssl(ref e) => e.description(),
             Error::Parse(ref e) => e.description(),
-            Error::BadResponse(_) => "Bad Response",
-            Error::NoResponse(_) => "No Response",
+            Error::BadResponse(..) => "Bad Response",
+            Error::NoResponse(..) => "No Response",
+            Error::ByeResponse(..) => "Bye Response",
+            Error::InvalidArgument(_) => "Invalid command argument",
         }
     }
 
@@ -66,12 +74,22 @@ impl StdError for Error {
 
 #[derive(Debug)]
 pub enum ParseError {
+    // Indicates an error parsing a response that does not follow the IMAP formal syntax.
+    Response(String),
     // Indicates an error parsing the status response. Such as OK, NO, and BAD.
-    StatusResponse(Vec<String>),
+    StatusResponse(String),
     // Error parsing the cabability response.
-    Capability(Vec<String>),
-    // Authentication errors.
-    Authentication(String)
+    Capability,
+    // Error parsing a FETCH response.
+    Fetch,
+    // Error parsing a LIST or LSUB response.
+    List,
+    // Error parsing a STATUS response.
+    Status,
+    // Error parsing a NAMESPACE response.
+    Namespace,
+    // Error parsing an ID response.
+    Id
 }
 
 impl fmt::Display for ParseError {
@@ -85,9 +103,14 @@ impl fmt::Display for ParseError {
 impl StdError for ParseError {
     fn description(&self) -> &str {
         match *self {
+            ParseError::Response(_) => "Unable to parse response",
             ParseError::StatusResponse(_) => "Unable to parse status response",
-            ParseError::Capability(_) => "Unable to parse capability response",
-            ParseError::Authentication(_) => "Unable to parse authentication response"
+            ParseError::Capability => "Unable to parse capability response",
+            ParseError::Fetch => "Unable to parse fetch response",
+            ParseError::List => "Unable to parse list response",
+            ParseError::Status => "Unable to parse status response",
+            ParseError::Namespace => "Unable to parse namespace response",
+            ParseError::Id => "Unable to parse id response"
         }
     }
 
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use super::body_structure::BodyStructure;
+use super::envelope::Envelope;
+use super::flag::Flag;
+
+/// The data items returned by a FETCH command for a single message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fetch {
+    /// The message sequence number.
+    pub message: u32,
+    pub uid: Option<u32>,
+    pub flags: Vec<Flag>,
+    pub internal_date: Option<String>,
+    pub rfc822_size: Option<u32>,
+    /// The mod-sequence value of the message, sent by servers with CONDSTORE (RFC 7162).
+    pub mod_seq: Option<u64>,
+    pub envelope: Option<Envelope>,
+    /// The MIME structure, as fetched with `BODYSTRUCTURE` or `BODY`.
+    pub body_structure: Option<BodyStructure>,
+    /// The fetched body sections, keyed by their upper case section spec. `BODY[]` and `RFC822`
+    /// are stored under `""`, `BODY[HEADER]` and `RFC822.HEADER` under `"HEADER"`, and so on.
+    pub sections: HashMap<String, Vec<u8>>
+}
+
+impl Fetch {
+    pub fn new(message: u32) -> Fetch {
+        Fetch {
+            message: message,
+            uid: None,
+            flags: Vec::new(),
+            internal_date: None,
+            rfc822_size: None,
+            mod_seq: None,
+            envelope: None,
+            body_structure: None,
+            sections: HashMap::new()
+        }
+    }
+
+    /// Returns the contents of a body section, e.g. `body("")` for the whole message or
+    /// `body("1.2")` for a single MIME part.
+    pub fn body(&self, section: &str) -> Option<&[u8]> {
+        self.sections.get(&section.to_uppercase()).map(|body| &body[..])
+    }
+
+    /// Returns the message header, as fetched with `BODY[HEADER]` or `RFC822.HEADER`.
+    pub fn header(&self) -> Option<&[u8]> {
+        self.body("HEADER")
+    }
+
+    /// Returns the message body without the header, as fetched with `BODY[TEXT]` or `RFC822.TEXT`.
+    pub fn text(&self) -> Option<&[u8]> {
+        self.body("TEXT")
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+
+use super::command::is_atom;
+use super::error::{Error, Result};
+
+/// A message flag, either one of the system flags of RFC 3501 or a keyword.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Flag {
+    Seen,
+    Answered,
+    Flagged,
+    Deleted,
+    Draft,
+    Recent,
+    /// `\*`, which is only used in PERMANENTFLAGS to indicate that new keywords can be created.
+    MayCreate,
+    /// Any other flag, e.g. `$Forwarded` or `$Junk`, as sent by the server.
+    Keyword(String)
+}
+
+impl Flag {
+    /// Checks if the flag can be sent to the server in STORE or APPEND. Keywords must be atoms,
+    /// optionally preceded by a backslash as in `\Important`. `\Recent` and `\*` are set by
+    /// the server only, so they are not valid.
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Flag::Recent | Flag::MayCreate => false,
+            Flag::Keyword(ref keyword) => {
+                let atom = if keyword.starts_with('\\') { &keyword[1..] } else { &keyword[..] };
+                is_atom(atom) && !keyword.eq_ignore_ascii_case("\\Recent")
+            },
+            _ => true
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Flag {
+    fn from(flag: &'a str) -> Flag {
+        match &flag.to_lowercase()[..] {
+            "\\seen" => Flag::Seen,
+            "\\answered" => Flag::Answered,
+            "\\flagged" => Flag::Flagged,
+            "\\deleted" => Flag::Deleted,
+            "\\draft" => Flag::Draft,
+            "\\recent" => Flag::Recent,
+            "\\*" => Flag::MayCreate,
+            _ => Flag::Keyword(flag.to_string())
+        }
+    }
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Flag::Seen => f.write_str("\\Seen"),
+            Flag::Answered => f.write_str("\\Answered"),
+            Flag::Flagged => f.write_str("\\Flagged"),
+            Flag::Deleted => f.write_str("\\Deleted"),
+            Flag::Draft => f.write_str("\\Draft"),
+            Flag::Recent => f.write_str("\\Recent"),
+            Flag::MayCreate => f.write_str("\\*"),
+            Flag::Keyword(ref keyword) => f.write_str(keyword)
+        }
+    }
+}
+
+/// Formats flags as a parenthesized list, e.g. `(\Seen $Forwarded)`. Fails if a keyword is not
+/// valid, since it could end the command line.
+pub fn flag_list(flags: &[Flag]) -> Result<String> {
+    if let Some(flag) = flags.iter().find(|flag| !flag.is_valid()) {
+        return Err(Error::InvalidArgument(format!("Invalid flag {:?}", flag.to_string())));
+    }
+    let flags: Vec<String> = flags.iter().map(|flag| flag.to_string()).collect();
+    Ok(format!("({})", flags.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_str() {
+        assert!(Flag::from("\\Seen") == Flag::Seen, "Unexpected flag");
+        assert!(Flag::from("\\DELETED") == Flag::Deleted, "System flags should be case-insensitive");
+        assert!(Flag::from("\\*") == Flag::MayCreate, "Unexpected flag");
+        assert!(Flag::from("$Forwarded") == Flag::Keyword(String::from("$Forwarded")), "Unexpected flag");
+    }
+
+    #[test]
+    fn display() {
+        assert!(flag_list(&[Flag::Seen, Flag::Keyword(String::from("$Junk"))]).unwrap() == "(\\Seen $Junk)", "Unexpected flag list");
+    }
+
+    #[test]
+    fn invalid_keywords() {
+        assert!(Flag::Keyword(String::from("\\Important")).is_valid(), "Flag extension should be valid");
+        for keyword in ["", "a b", "x)\r\na2 DELETE INBOX", "[Gmail]", "\\", "\\\\x", "Grüße"].iter() {
+            assert!(!Flag::Keyword(keyword.to_string()).is_valid(), "Keyword {:?} should be invalid", keyword);
+        }
+        assert!(flag_list(&[Flag::Keyword(String::from("x)"))]).is_err(), "Invalid keyword should fail");
+        assert!(!Flag::Recent.is_valid() && !Flag::MayCreate.is_valid(), "Server flags should be invalid");
+        assert!(!Flag::Keyword(String::from("\\RECENT")).is_valid(), "\\Recent keyword should be invalid");
+        assert!(flag_list(&[Flag::Seen, Flag::Recent]).is_err(), "\\Recent should fail");
+    }
+}
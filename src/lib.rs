@@ -4,12 +4,24 @@
 //! imap is a IMAP client for Rust.
 
 extern crate openssl;
-extern crate regex;
 
 pub mod authenticator;
+pub mod body_structure;
+pub mod capabilities;
 pub mod client;
+pub mod command;
+pub mod envelope;
 pub mod error;
+pub mod fetch;
+pub mod flag;
 pub mod mailbox;
+pub mod name;
+pub mod namespace;
+pub mod response;
+pub mod search;
+pub mod sequence_set;
+pub mod store;
+pub mod utf7;
 
 mod parse;
 
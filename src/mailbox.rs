@@ -1,32 +1,86 @@
 use std::fmt;
 
+use super::flag::Flag;
+
 #[derive(Eq,PartialEq)]
 pub struct Mailbox {
-	pub flags: String,
+	pub flags: Vec<Flag>,
 	pub exists: u32,
 	pub recent: u32,
 	pub unseen: Option<u32>,
-	pub permanent_flags: Option<String>,
+	pub permanent_flags: Option<Vec<Flag>>,
 	pub uid_next: Option<u32>,
-	pub uid_validity: Option<u32>
+	pub uid_validity: Option<u32>,
+	/// The highest mod-sequence value, if the server supports CONDSTORE (RFC 7162).
+	pub highest_mod_seq: Option<u64>
 }
 
 impl Default for Mailbox {
 	fn default() -> Mailbox {
 		Mailbox {
-			flags: "".to_string(),
+			flags: Vec::new(),
 			exists: 0,
 			recent: 0,
 			unseen: None,
 			permanent_flags: None,
 			uid_next: None,
-			uid_validity: None
+			uid_validity: None,
+			highest_mod_seq: None
 		}
 	}
 }
 
 impl fmt::Display for Mailbox {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "flags: {}, exists: {}, recent: {}, unseen: {:?}, permanent_flags: {:?}, uid_next: {:?}, uid_validity: {:?}", self.flags, self.exists, self.recent, self.unseen, self.permanent_flags, self.uid_next, self.uid_validity)
+        write!(f, "flags: {:?}, exists: {}, recent: {}, unseen: {:?}, permanent_flags: {:?}, uid_next: {:?}, uid_validity: {:?}, highest_mod_seq: {:?}", self.flags, self.exists, self.recent, self.unseen, self.permanent_flags, self.uid_next, self.uid_validity, self.highest_mod_seq)
     }
 }
+
+/// A status data item that can be requested with the STATUS command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusItem {
+	/// The number of messages in the mailbox.
+	Messages,
+	/// The number of messages with the `\Recent` flag set.
+	Recent,
+	/// The next unique identifier value of the mailbox.
+	UidNext,
+	/// The unique identifier validity value of the mailbox.
+	UidValidity,
+	/// The number of messages which do not have the `\Seen` flag set.
+	Unseen,
+	/// The highest mod-sequence value of the mailbox. Requires the CONDSTORE capability.
+	HighestModSeq,
+	/// The total size of the mailbox in octets. Requires the STATUS=SIZE capability.
+	Size,
+	/// The number of messages with the `\Deleted` flag set. Requires IMAP4rev2.
+	Deleted
+}
+
+impl fmt::Display for StatusItem {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			StatusItem::Messages => "MESSAGES",
+			StatusItem::Recent => "RECENT",
+			StatusItem::UidNext => "UIDNEXT",
+			StatusItem::UidValidity => "UIDVALIDITY",
+			StatusItem::Unseen => "UNSEEN",
+			StatusItem::HighestModSeq => "HIGHESTMODSEQ",
+			StatusItem::Size => "SIZE",
+			StatusItem::Deleted => "DELETED"
+		})
+	}
+}
+
+/// The status of a mailbox as returned by the STATUS command. Only the requested items are set.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct MailboxStatus {
+	pub messages: Option<u32>,
+	pub recent: Option<u32>,
+	pub uid_next: Option<u32>,
+	pub uid_validity: Option<u32>,
+	pub unseen: Option<u32>,
+	pub highest_mod_seq: Option<u64>,
+	pub size: Option<u64>,
+	pub deleted: Option<u32>
+}
@@ -1,10 +1,17 @@
 use std::io::{Read, Result, Write, Error, ErrorKind};
+use std::thread;
+use std::time::Duration;
+
+use super::client::SetReadTimeout;
+use super::error;
 
 pub struct MockStream {
     read_buf: Vec<u8>,
     read_pos: usize,
     pub written_buf: Vec<u8>,
-    err_on_read: bool
+    err_on_read: bool,
+    timeout_pos: Option<usize>,
+    read_timeout: Option<Duration>
 }
 
 impl MockStream {
@@ -13,7 +20,9 @@ impl MockStream {
             read_buf: read_buf,
             read_pos: 0,
             written_buf: Vec::new(),
-            err_on_read: false
+            err_on_read: false,
+            timeout_pos: None,
+            read_timeout: None
         }
     }
 
@@ -22,9 +31,18 @@ impl MockStream {
             read_buf: Vec::new(),
             read_pos: 0,
             written_buf: Vec::new(),
-            err_on_read: true
+            err_on_read: true,
+            timeout_pos: None,
+            read_timeout: None
         }
     }
+
+    /// Makes the first read at the position time out after the read timeout, as a read on a
+    /// socket does when the server sends nothing.
+    pub fn timeout_at(mut self, pos: usize) -> MockStream {
+        self.timeout_pos = Some(pos);
+        self
+    }
 }
 
 impl Read for MockStream {
@@ -35,7 +53,19 @@ impl Read for MockStream {
         if self.read_pos >= self.read_buf.len() {
             return Err(Error::new(ErrorKind::UnexpectedEof, "EOF"))
         }
-        let write_len = min(buf.len(), self.read_buf.len() - self.read_pos);
+        let mut available = self.read_buf.len() - self.read_pos;
+        if let Some(pos) = self.timeout_pos {
+            if pos == self.read_pos {
+                self.timeout_pos = None;
+                if let Some(timeout) = self.read_timeout {
+                    thread::sleep(timeout);
+                }
+                return Err(Error::new(ErrorKind::TimedOut, "MockStream timeout"))
+            } else if pos > self.read_pos {
+                available = min(available, pos - self.read_pos);
+            }
+        }
+        let write_len = min(buf.len(), available);
         let max_pos = self.read_pos + write_len;
         for x in self.read_pos..max_pos {
             buf[x - self.read_pos] = self.read_buf[x];
@@ -56,6 +86,13 @@ impl Write for MockStream {
     }
 }
 
+impl SetReadTimeout for MockStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> error::Result<()> {
+        self.read_timeout = timeout;
+        Ok(())
+    }
+}
+
 fn min(a: usize, b: usize) -> usize {
     if a < b {
         a
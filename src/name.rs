@@ -0,0 +1,68 @@
+/// A mailbox name as returned by the LIST and LSUB commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Name {
+    pub attributes: Vec<NameAttribute>,
+    /// The hierarchy delimiter, or `None` if the server has no hierarchy.
+    pub delimiter: Option<String>,
+    pub name: String
+}
+
+impl Name {
+    /// Checks if the mailbox can be selected, i.e. it is not `\Noselect` or `\NonExistent`.
+    pub fn is_selectable(&self) -> bool {
+        !self.attributes.iter().any(|a| *a == NameAttribute::NoSelect || *a == NameAttribute::NonExistent)
+    }
+
+    /// Checks if the mailbox has the given attribute.
+    pub fn has_attribute(&self, attribute: &NameAttribute) -> bool {
+        self.attributes.contains(attribute)
+    }
+}
+
+/// A mailbox name attribute, including the LIST-EXTENDED (RFC 5258) and SPECIAL-USE (RFC 6154)
+/// attributes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameAttribute {
+    NoInferiors,
+    NoSelect,
+    Marked,
+    Unmarked,
+    NonExistent,
+    Subscribed,
+    Remote,
+    HasChildren,
+    HasNoChildren,
+    All,
+    Archive,
+    Drafts,
+    Flagged,
+    Junk,
+    Sent,
+    Trash,
+    /// Any other attribute, as sent by the server.
+    Custom(String)
+}
+
+impl<'a> From<&'a str> for NameAttribute {
+    fn from(attribute: &'a str) -> NameAttribute {
+        match &attribute.to_lowercase()[..] {
+            "\\noinferiors" => NameAttribute::NoInferiors,
+            "\\noselect" => NameAttribute::NoSelect,
+            "\\marked" => NameAttribute::Marked,
+            "\\unmarked" => NameAttribute::Unmarked,
+            "\\nonexistent" => NameAttribute::NonExistent,
+            "\\subscribed" => NameAttribute::Subscribed,
+            "\\remote" => NameAttribute::Remote,
+            "\\haschildren" => NameAttribute::HasChildren,
+            "\\hasnochildren" => NameAttribute::HasNoChildren,
+            "\\all" => NameAttribute::All,
+            "\\archive" => NameAttribute::Archive,
+            "\\drafts" => NameAttribute::Drafts,
+            "\\flagged" => NameAttribute::Flagged,
+            "\\junk" => NameAttribute::Junk,
+            "\\sent" => NameAttribute::Sent,
+            "\\trash" => NameAttribute::Trash,
+            _ => NameAttribute::Custom(attribute.to_string())
+        }
+    }
+}
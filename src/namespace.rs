@@ -0,0 +1,20 @@
+/// A namespace as returned by the NAMESPACE command (RFC 2342): the prefix under which its
+/// mailboxes are found and their hierarchy delimiter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Namespace {
+    /// The prefix of the mailbox names, e.g. `INBOX.` or `#shared/`, or `""` for the root.
+    pub prefix: String,
+    /// The hierarchy delimiter, or `None` if the namespace has no hierarchy.
+    pub delimiter: Option<String>
+}
+
+/// The namespaces of the server, each of which may be empty if the server has none of that kind.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Namespaces {
+    /// The namespaces of the user's own mailboxes.
+    pub personal: Vec<Namespace>,
+    /// The namespaces of the mailboxes of other users.
+    pub other_users: Vec<Namespace>,
+    /// The namespaces of mailboxes shared between users.
+    pub shared: Vec<Namespace>
+}
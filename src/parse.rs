@@ -1,148 +1,1157 @@
-use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::result;
+use std::str;
 
-use super::mailbox::Mailbox;
+use super::capabilities::Capabilities;
+use super::body_structure::{BodyStructure, Disposition, SinglePart};
+use super::envelope::{Address, Envelope};
+use super::fetch::Fetch;
+use super::flag::Flag;
+use super::mailbox::{Mailbox, MailboxStatus};
+use super::name::{Name, NameAttribute};
+use super::namespace::{Namespace, Namespaces};
+use super::response::{Response, ResponseCode, Status, UnsolicitedResponse, Value};
+use super::sequence_set::SequenceSet;
 use super::error::{Error, ParseError, Result};
 
-pub fn parse_authenticate_response(line: String) -> Result<String> {
-    let authenticate_regex = Regex::new("^+(.*)\r\n").unwrap();
+const SP: u8 = b' ';
+const CR: u8 = 0x0d;
+const LF: u8 = 0x0a;
 
-    for cap in authenticate_regex.captures_iter(line.as_str()) {
-        let data = cap.at(1).unwrap_or("");
-        return Ok(String::from(data));
+/// The largest number of UIDs of a COPYUID response code that is expanded into pairs; larger
+/// ones are kept as `ResponseCode::Other`.
+const MAX_COPIED_UIDS: u64 = 1000000;
+
+/// The untagged data that the client parses; its values must follow the grammar.
+const KNOWN_DATA: &'static [&'static str] = &[
+    "CAPABILITY", "ENABLED", "LIST", "LSUB", "STATUS", "SEARCH", "FLAGS", "NAMESPACE", "ID",
+    "VANISHED", "EXISTS", "RECENT", "EXPUNGE", "FETCH"
+];
+
+type ParseResult<T> = result::Result<T, ()>;
+
+/// Parses a single response, as read by the client, including any literals and the trailing CRLF.
+pub fn parse_response(data: &[u8]) -> Result<Response> {
+    let mut parser = Parser { data: data, pos: 0, literal_needed: None };
+    parser.response().map_err(|_| {
+        Error::Parse(ParseError::Response(String::from_utf8_lossy(data).into_owned()))
+    })
+}
+
+/// Returns the octet count of a literal announced at the end of the data read so far, if the
+/// response continues with it. A `{N}` in the text of a status response is not a literal.
+pub fn literal_needed(data: &[u8]) -> Option<usize> {
+    let mut parser = Parser { data: data, pos: 0, literal_needed: None };
+    match parser.response() {
+        Ok(_) => None,
+        Err(_) => parser.literal_needed
     }
+}
 
-    Err(Error::Parse(ParseError::Authentication(line)))
+/// Checks the tagged status response that completes a command and returns the untagged
+/// responses that came before it.
+pub fn check_response(mut responses: Vec<Response>) -> Result<Vec<Response>> {
+    match responses.pop() {
+        Some(Response::Status { tag: Some(_), status, code, information }) => {
+            match status {
+                Status::Ok => Ok(responses),
+                Status::No => Err(Error::NoResponse(code, information)),
+                Status::Bad => Err(Error::BadResponse(code, information)),
+                _ => Err(Error::Parse(ParseError::StatusResponse(information)))
+            }
+        },
+        _ => Err(Error::Parse(ParseError::StatusResponse(String::new())))
+    }
 }
 
-pub fn parse_capability(lines: Vec<String>) -> Result<Vec<String>> {
-    let capability_regex = Regex::new(r"^\* CAPABILITY (.*)\r\n").unwrap();
+pub fn parse_capability(responses: &[Response]) -> Result<Capabilities> {
+    for response in responses.iter() {
+        if let Some(values) = response.data("CAPABILITY") {
+            return Ok(capabilities_from_values(values));
+        }
+    }
 
-    //Check Ok
-    match parse_response_ok(lines.clone()) {
-        Ok(_) => (),
-        Err(e) => return Err(e)
-    };
+    Err(Error::Parse(ParseError::Capability))
+}
+
+/// Returns the capabilities sent in a response, either as CAPABILITY data or as a CAPABILITY
+/// response code, e.g. in the greeting or after LOGIN.
+pub fn parse_capabilities_in(response: &Response) -> Option<Capabilities> {
+    if let Some(values) = response.data("CAPABILITY") {
+        return Some(capabilities_from_values(values));
+    }
+    match response.code() {
+        Some(&ResponseCode::Capability(ref capabilities)) => Some(capabilities.clone()),
+        _ => None
+    }
+}
+
+/// Parses the extensions listed in ENABLED responses.
+pub fn parse_enabled(responses: &[Response]) -> Result<Capabilities> {
+    let mut enabled = Vec::new();
+    for response in responses.iter() {
+        if let Some(values) = response.data("ENABLED") {
+            enabled.extend(values.iter().filter_map(Value::as_string));
+        }
+    }
+    Ok(Capabilities::new(enabled))
+}
+
+fn capabilities_from_values(values: &[Value]) -> Capabilities {
+    Capabilities::new(values.iter().filter_map(Value::as_string).collect())
+}
+
+pub fn parse_select_or_examine(responses: &[Response]) -> Result<Mailbox> {
+    let mut mailbox = Mailbox::default();
+
+    for response in responses.iter() {
+        if let Some((exists, _)) = response.numbered_data("EXISTS") {
+            mailbox.exists = exists;
+        } else if let Some((recent, _)) = response.numbered_data("RECENT") {
+            mailbox.recent = recent;
+        } else if let Some(values) = response.data("FLAGS") {
+            mailbox.flags = parse_flags(values.first());
+        } else if let Some(code) = response.code() {
+            match *code {
+                ResponseCode::Unseen(unseen) => mailbox.unseen = Some(unseen),
+                ResponseCode::UidValidity(uid_validity) => mailbox.uid_validity = Some(uid_validity),
+                ResponseCode::UidNext(uid_next) => mailbox.uid_next = Some(uid_next),
+                ResponseCode::HighestModSeq(mod_seq) => mailbox.highest_mod_seq = Some(mod_seq),
+                ResponseCode::PermanentFlags(ref flags) => {
+                    mailbox.permanent_flags = Some(flags.clone());
+                },
+                _ => {}
+            }
+        }
+    }
+
+    Ok(mailbox)
+}
 
-    for line in lines.iter() {
-        if capability_regex.is_match(line) {
-            let cap = capability_regex.captures(line).unwrap();
-            let capabilities_str = cap.at(1).unwrap();
-            return Ok(capabilities_str.split(' ').map(|x| x.to_string()).collect());
+pub fn parse_status(responses: &[Response]) -> Result<MailboxStatus> {
+    for response in responses.iter() {
+        if let Some(values) = response.data("STATUS") {
+            let items = match values.get(1).and_then(Value::as_list) {
+                Some(items) if items.len() % 2 == 0 => items,
+                _ => return Err(Error::Parse(ParseError::Status))
+            };
+            let mut status = MailboxStatus::default();
+            for pair in items.chunks(2) {
+                let value = &pair[1];
+                match &pair[0].as_atom().unwrap_or("").to_uppercase()[..] {
+                    "MESSAGES" => status.messages = value.as_number(),
+                    "RECENT" => status.recent = value.as_number(),
+                    "UIDNEXT" => status.uid_next = value.as_number(),
+                    "UIDVALIDITY" => status.uid_validity = value.as_number(),
+                    "UNSEEN" => status.unseen = value.as_number(),
+                    "HIGHESTMODSEQ" => status.highest_mod_seq = value.as_u64(),
+                    "SIZE" => status.size = value.as_u64(),
+                    "DELETED" => status.deleted = value.as_number(),
+                    _ => {}
+                }
+            }
+            return Ok(status);
         }
     }
 
-    Err(Error::Parse(ParseError::Capability(lines)))
+    Err(Error::Parse(ParseError::Status))
 }
 
-pub fn parse_response_ok(lines: Vec<String>) -> Result<()> {
-    match parse_response(lines) {
-        Ok(_) => Ok(()),
-        Err(e) => return Err(e)
+/// Parses the message numbers of SEARCH responses. A server may send several of them.
+pub fn parse_search(responses: &[Response]) -> Result<HashSet<u32>> {
+    let mut numbers = HashSet::new();
+    for response in responses.iter() {
+        if let Some(values) = response.data("SEARCH") {
+            numbers.extend(values.iter().filter_map(Value::as_number));
+        }
+    }
+    Ok(numbers)
+}
+
+/// Parses the LIST or LSUB responses, depending on `data_name`.
+pub fn parse_names(responses: &[Response], data_name: &str) -> Result<Vec<Name>> {
+    let mut names = Vec::new();
+    for response in responses.iter() {
+        if let Some(values) = response.data(data_name) {
+            if values.len() < 3 {
+                return Err(Error::Parse(ParseError::List));
+            }
+            let attributes = match values[0].as_list() {
+                Some(attributes) => attributes.iter().filter_map(Value::as_atom).map(NameAttribute::from).collect(),
+                None => return Err(Error::Parse(ParseError::List))
+            };
+            let name = match values[2].as_string() {
+                Some(name) => name,
+                None => return Err(Error::Parse(ParseError::List))
+            };
+            names.push(Name { attributes: attributes, delimiter: values[1].as_nstring(), name: name });
+        }
+    }
+    Ok(names)
+}
+
+/// Parses the UIDs of VANISHED responses, e.g. the messages expunged since the last QRESYNC.
+pub fn parse_vanished(responses: &[Response]) -> Result<SequenceSet> {
+    let mut uids = SequenceSet::new();
+    for response in responses.iter() {
+        if let Some(values) = response.data("VANISHED") {
+            uids.insert_set(&try!(vanished_uids(values)));
+        }
     }
+    Ok(uids)
 }
 
-pub fn parse_response(lines: Vec<String>) -> Result<Vec<String>> {
-    let regex = Regex::new(r"^([a-zA-Z0-9]+) (OK|NO|BAD)(.*)").unwrap();
-    let last_line = match lines.last() {
-        Some(l) => l,
-        None => return Err(Error::Parse(ParseError::StatusResponse(lines.clone())))
+/// Parses the UIDs of a VANISHED response, which may start with `(EARLIER)`.
+fn vanished_uids(values: &[Value]) -> Result<SequenceSet> {
+    match values.last().and_then(Value::as_atom).and_then(|uids| uids.parse().ok()) {
+        Some(uids) => Ok(uids),
+        None => Err(Error::Parse(ParseError::Fetch))
+    }
+}
+
+/// Parses the personal, other users' and shared namespaces of a NAMESPACE response.
+pub fn parse_namespaces(responses: &[Response]) -> Result<Namespaces> {
+    for response in responses.iter() {
+        if let Some(values) = response.data("NAMESPACE") {
+            if values.len() < 3 {
+                return Err(Error::Parse(ParseError::Namespace));
+            }
+            return Ok(Namespaces {
+                personal: try!(parse_namespace_list(&values[0])),
+                other_users: try!(parse_namespace_list(&values[1])),
+                shared: try!(parse_namespace_list(&values[2]))
+            });
+        }
+    }
+
+    Err(Error::Parse(ParseError::Namespace))
+}
+
+/// Parses a list of namespaces, or NIL. Extensions after the delimiter are ignored.
+fn parse_namespace_list(value: &Value) -> Result<Vec<Namespace>> {
+    let list = match *value {
+        Value::Nil => return Ok(Vec::new()),
+        Value::List(ref list) => list,
+        _ => return Err(Error::Parse(ParseError::Namespace))
     };
+    let mut namespaces = Vec::new();
+    for namespace in list.iter() {
+        match namespace.as_list() {
+            Some(fields) if fields.len() >= 2 => match fields[0].as_string() {
+                Some(prefix) => namespaces.push(Namespace { prefix: prefix, delimiter: fields[1].as_nstring() }),
+                None => return Err(Error::Parse(ParseError::Namespace))
+            },
+            _ => return Err(Error::Parse(ParseError::Namespace))
+        }
+    }
+    Ok(namespaces)
+}
+
+/// Parses the fields of an ID response. The server may send NIL instead of the fields, and NIL
+/// instead of a value; such fields are left out.
+pub fn parse_id(responses: &[Response]) -> Result<HashMap<String, String>> {
+    for response in responses.iter() {
+        if let Some(values) = response.data("ID") {
+            let fields = match values.first() {
+                Some(&Value::Nil) => return Ok(HashMap::new()),
+                Some(&Value::List(ref fields)) if fields.len() % 2 == 0 => fields,
+                _ => return Err(Error::Parse(ParseError::Id))
+            };
+            let mut id = HashMap::new();
+            for pair in fields.chunks(2) {
+                match (pair[0].as_string(), pair[1].as_nstring()) {
+                    (Some(name), Some(value)) => { id.insert(name, value); },
+                    (Some(_), None) => {},
+                    (None, _) => return Err(Error::Parse(ParseError::Id))
+                }
+            }
+            return Ok(id);
+        }
+    }
+
+    Err(Error::Parse(ParseError::Id))
+}
 
-    for cap in regex.captures_iter(last_line) {
-        let response_type = cap.at(2).unwrap_or("");
-        match response_type {
-            "OK" => return Ok(lines.clone()),
-            "BAD" => return Err(Error::BadResponse(lines.clone())),
-            "NO" => return Err(Error::NoResponse(lines.clone())),
-            _ => {}
+/// Parses EXISTS, RECENT, EXPUNGE, FETCH and VANISHED responses into mailbox updates. Other
+/// responses are not mailbox updates and give `None`.
+pub fn parse_unsolicited(response: &Response) -> Result<Option<UnsolicitedResponse>> {
+    if let Some(values) = response.data("VANISHED") {
+        return Ok(Some(UnsolicitedResponse::Vanished(try!(vanished_uids(values)))));
+    }
+    if let Response::Data { number: Some(number), ref name, ref values } = *response {
+        return match &name[..] {
+            "EXISTS" => Ok(Some(UnsolicitedResponse::Exists(number))),
+            "RECENT" => Ok(Some(UnsolicitedResponse::Recent(number))),
+            "EXPUNGE" => Ok(Some(UnsolicitedResponse::Expunge(number))),
+            "FETCH" => match values.first().and_then(Value::as_list) {
+                Some(items) => Ok(Some(UnsolicitedResponse::Fetch(try!(parse_fetch(number, items))))),
+                None => Err(Error::Parse(ParseError::Fetch))
+            },
+            _ => Ok(None)
+        };
+    }
+    Ok(None)
+}
+
+pub fn parse_fetches(responses: &[Response]) -> Result<Vec<Fetch>> {
+    let mut fetches = Vec::new();
+    for response in responses.iter() {
+        if let Some((message, values)) = response.numbered_data("FETCH") {
+            match values.first().and_then(Value::as_list) {
+                Some(items) => fetches.push(try!(parse_fetch(message, items))),
+                None => return Err(Error::Parse(ParseError::Fetch))
+            }
         }
     }
+    Ok(fetches)
+}
+
+/// Parses the list of data item names and values of a single FETCH response.
+pub fn parse_fetch(message: u32, items: &[Value]) -> Result<Fetch> {
+    if items.len() % 2 != 0 {
+        return Err(Error::Parse(ParseError::Fetch));
+    }
 
-    Err(Error::Parse(ParseError::StatusResponse(lines.clone())))
+    let mut fetch = Fetch::new(message);
+    for pair in items.chunks(2) {
+        let name = match pair[0].as_atom() {
+            Some(name) => name.to_uppercase(),
+            None => return Err(Error::Parse(ParseError::Fetch))
+        };
+        let value = &pair[1];
+        match &name[..] {
+            "UID" => fetch.uid = value.as_number(),
+            "FLAGS" => fetch.flags = parse_flags(Some(value)),
+            "INTERNALDATE" => fetch.internal_date = value.as_nstring(),
+            "RFC822.SIZE" => fetch.rfc822_size = value.as_number(),
+            "MODSEQ" => fetch.mod_seq = value.as_list().and_then(|list| list.first()).and_then(Value::as_u64),
+            "ENVELOPE" => fetch.envelope = Some(try!(parse_envelope(value))),
+            "BODYSTRUCTURE" | "BODY" => fetch.body_structure = Some(try!(parse_body_structure(value))),
+            "RFC822" => insert_section(&mut fetch, String::new(), value),
+            "RFC822.HEADER" => insert_section(&mut fetch, String::from("HEADER"), value),
+            "RFC822.TEXT" => insert_section(&mut fetch, String::from("TEXT"), value),
+            _ => {
+                if name.starts_with("BODY[") {
+                    let end = name.rfind(']').unwrap_or(name.len());
+                    let section = name[5..end].to_string();
+                    insert_section(&mut fetch, section, value);
+                }
+            }
+        }
+    }
+    Ok(fetch)
 }
 
-pub fn parse_select_or_examine(lines: Vec<String>) -> Result<Mailbox> {
-    let exists_regex = Regex::new(r"^\* (\d+) EXISTS\r\n").unwrap();
+pub fn parse_envelope(value: &Value) -> Result<Envelope> {
+    let fields = match value.as_list() {
+        Some(fields) if fields.len() == 10 => fields,
+        _ => return Err(Error::Parse(ParseError::Fetch))
+    };
+    Ok(Envelope {
+        date: fields[0].as_nstring(),
+        subject: fields[1].as_nstring(),
+        from: try!(parse_addresses(&fields[2])),
+        sender: try!(parse_addresses(&fields[3])),
+        reply_to: try!(parse_addresses(&fields[4])),
+        to: try!(parse_addresses(&fields[5])),
+        cc: try!(parse_addresses(&fields[6])),
+        bcc: try!(parse_addresses(&fields[7])),
+        in_reply_to: fields[8].as_nstring(),
+        message_id: fields[9].as_nstring()
+    })
+}
+
+/// Parses a list of addresses. Group syntax is sent as a start marker (NIL host, group name as
+/// mailbox) and an end marker (NIL mailbox and host); the markers are removed and the addresses
+/// in between get the group name.
+fn parse_addresses(value: &Value) -> Result<Vec<Address>> {
+    let mut addresses = Vec::new();
+    let list = match *value {
+        Value::Nil => return Ok(addresses),
+        Value::List(ref list) => list,
+        _ => return Err(Error::Parse(ParseError::Fetch))
+    };
+
+    let mut group = None;
+    for address in list.iter() {
+        let fields = match address.as_list() {
+            Some(fields) if fields.len() == 4 => fields,
+            _ => return Err(Error::Parse(ParseError::Fetch))
+        };
+        let mailbox = fields[2].as_nstring();
+        let host = fields[3].as_nstring();
+        match (mailbox, host) {
+            (None, None) => group = None,
+            (Some(name), None) => group = Some(name),
+            (mailbox, host) => addresses.push(Address {
+                name: fields[0].as_nstring(),
+                adl: fields[1].as_nstring(),
+                mailbox: mailbox,
+                host: host,
+                group: group.clone()
+            })
+        }
+    }
+    Ok(addresses)
+}
+
+pub fn parse_body_structure(value: &Value) -> Result<BodyStructure> {
+    let fields = match value.as_list() {
+        Some(fields) if !fields.is_empty() => fields,
+        _ => return Err(Error::Parse(ParseError::Fetch))
+    };
+
+    if fields[0].as_list().is_some() {
+        let count = fields.iter().take_while(|f| f.as_list().is_some()).count();
+        let mut bodies = Vec::new();
+        for body in fields[..count].iter() {
+            bodies.push(try!(parse_body_structure(body)));
+        }
+        let subtype = match fields.get(count).and_then(Value::as_string) {
+            Some(subtype) => subtype,
+            None => return Err(Error::Parse(ParseError::Fetch))
+        };
+        let extension = &fields[count + 1..];
+        return Ok(BodyStructure::Multipart {
+            subtype: subtype,
+            bodies: bodies,
+            params: try!(parse_body_params(extension.get(0))),
+            disposition: try!(parse_disposition(extension.get(1))),
+            language: parse_language(extension.get(2)),
+            location: extension.get(3).and_then(Value::as_nstring)
+        });
+    }
+
+    if fields.len() < 7 {
+        return Err(Error::Parse(ParseError::Fetch));
+    }
+    let mut part = SinglePart {
+        media_type: fields[0].as_string().unwrap_or_default(),
+        media_subtype: fields[1].as_string().unwrap_or_default(),
+        params: try!(parse_body_params(Some(&fields[2]))),
+        id: fields[3].as_nstring(),
+        description: fields[4].as_nstring(),
+        encoding: fields[5].as_string().unwrap_or_default(),
+        size: fields[6].as_number().unwrap_or(0),
+        ..SinglePart::default()
+    };
+    let media_type = part.media_type.to_uppercase();
+    let media_subtype = part.media_subtype.to_uppercase();
 
-    let recent_regex = Regex::new(r"^\* (\d+) RECENT\r\n").unwrap();
+    let is_message = media_type == "MESSAGE" && (media_subtype == "RFC822" || media_subtype == "GLOBAL") &&
+        fields.len() >= 10 && fields[8].as_list().is_some();
+    let is_text = media_type == "TEXT" && fields.len() >= 8;
+    let extension = if is_message {
+        &fields[10..]
+    } else if is_text {
+        &fields[8..]
+    } else {
+        &fields[7..]
+    };
+    part.md5 = extension.get(0).and_then(Value::as_nstring);
+    part.disposition = try!(parse_disposition(extension.get(1)));
+    part.language = parse_language(extension.get(2));
+    part.location = extension.get(3).and_then(Value::as_nstring);
 
-    let flags_regex = Regex::new(r"^\* FLAGS (.+)\r\n").unwrap();
+    if is_message {
+        Ok(BodyStructure::Message {
+            part: part,
+            envelope: try!(parse_envelope(&fields[7])),
+            body: Box::new(try!(parse_body_structure(&fields[8]))),
+            lines: fields[9].as_number().unwrap_or(0)
+        })
+    } else if is_text {
+        Ok(BodyStructure::Text { part: part, lines: fields[7].as_number().unwrap_or(0) })
+    } else {
+        Ok(BodyStructure::Basic { part: part })
+    }
+}
 
-    let unseen_regex = Regex::new(r"^\* OK \[UNSEEN (\d+)\](.*)\r\n").unwrap();
+fn parse_body_params(value: Option<&Value>) -> Result<Vec<(String, String)>> {
+    let list = match value {
+        Some(&Value::List(ref list)) => list,
+        None | Some(&Value::Nil) => return Ok(Vec::new()),
+        _ => return Err(Error::Parse(ParseError::Fetch))
+    };
+    Ok(list.chunks(2).filter(|pair| pair.len() == 2).filter_map(|pair| {
+        match (pair[0].as_string(), pair[1].as_string()) {
+            (Some(name), Some(value)) => Some((name, value)),
+            _ => None
+        }
+    }).collect())
+}
 
-    let uid_validity_regex = Regex::new(r"^\* OK \[UIDVALIDITY (\d+)\](.*)\r\n").unwrap();
+fn parse_disposition(value: Option<&Value>) -> Result<Option<Disposition>> {
+    let list = match value {
+        Some(&Value::List(ref list)) if !list.is_empty() => list,
+        None | Some(&Value::Nil) => return Ok(None),
+        _ => return Err(Error::Parse(ParseError::Fetch))
+    };
+    Ok(Some(Disposition {
+        kind: list[0].as_string().unwrap_or_default(),
+        params: try!(parse_body_params(list.get(1)))
+    }))
+}
 
-    let uid_next_regex = Regex::new(r"^\* OK \[UIDNEXT (\d+)\](.*)\r\n").unwrap();
+fn parse_language(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(&Value::List(ref list)) => list.iter().filter_map(Value::as_string).collect(),
+        Some(value) => value.as_nstring().into_iter().collect(),
+        None => Vec::new()
+    }
+}
 
-    let permanent_flags_regex = Regex::new(r"^\* OK \[PERMANENTFLAGS (.+)\](.*)\r\n").unwrap();
+fn insert_section(fetch: &mut Fetch, section: String, value: &Value) {
+    if let Some(body) = value.as_bytes() {
+        fetch.sections.insert(section, body.to_vec());
+    }
+}
 
-    //Check Ok
-    match parse_response_ok(lines.clone()) {
-        Ok(_) => (),
-        Err(e) => return Err(e)
+/// Builds a response code from its name and the raw text of its arguments. The arguments are
+/// parsed only for the codes the client knows; other codes, and known codes with arguments that
+/// do not match their grammar, are kept as `Other` with the raw text.
+fn response_code(name: String, arguments: &[u8]) -> ResponseCode {
+    let code = match &name[..] {
+        "ALERT" => Some(ResponseCode::Alert),
+        "PARSE" => Some(ResponseCode::Parse),
+        "READ-ONLY" => Some(ResponseCode::ReadOnly),
+        "READ-WRITE" => Some(ResponseCode::ReadWrite),
+        "TRYCREATE" => Some(ResponseCode::TryCreate),
+        "UNAVAILABLE" => Some(ResponseCode::Unavailable),
+        "AUTHENTICATIONFAILED" => Some(ResponseCode::AuthenticationFailed),
+        "AUTHORIZATIONFAILED" => Some(ResponseCode::AuthorizationFailed),
+        "EXPIRED" => Some(ResponseCode::Expired),
+        "PRIVACYREQUIRED" => Some(ResponseCode::PrivacyRequired),
+        "CONTACTADMIN" => Some(ResponseCode::ContactAdmin),
+        "NOPERM" => Some(ResponseCode::NoPerm),
+        "INUSE" => Some(ResponseCode::InUse),
+        "EXPUNGEISSUED" => Some(ResponseCode::ExpungeIssued),
+        "CORRUPTION" => Some(ResponseCode::Corruption),
+        "SERVERBUG" => Some(ResponseCode::ServerBug),
+        "CLIENTBUG" => Some(ResponseCode::ClientBug),
+        "CANNOT" => Some(ResponseCode::Cannot),
+        "LIMIT" => Some(ResponseCode::Limit),
+        "OVERQUOTA" => Some(ResponseCode::OverQuota),
+        "ALREADYEXISTS" => Some(ResponseCode::AlreadyExists),
+        "NONEXISTENT" => Some(ResponseCode::NonExistent),
+        "NOMODSEQ" => Some(ResponseCode::NoModSeq),
+        "BADCHARSET" | "CAPABILITY" | "PERMANENTFLAGS" | "UIDNEXT" | "UIDVALIDITY" | "UNSEEN" | "APPENDUID" |
+        "COPYUID" | "HIGHESTMODSEQ" | "MODIFIED" => {
+            let mut parser = Parser { data: arguments, pos: 0, literal_needed: None };
+            match parser.values() {
+                Ok(ref values) if parser.pos == arguments.len() => response_code_with_values(&name, values),
+                _ => None
+            }
+        },
+        _ => None
     };
+    code.unwrap_or_else(|| {
+        let text = if arguments.is_empty() { None } else { Some(String::from_utf8_lossy(arguments).into_owned()) };
+        ResponseCode::Other(name, text)
+    })
+}
 
-    let mut mailbox = Mailbox::default();
+fn response_code_with_values(name: &str, values: &[Value]) -> Option<ResponseCode> {
+    let number = values.first().and_then(Value::as_number);
+    match (name, number) {
+        ("BADCHARSET", _) => Some(ResponseCode::BadCharset(string_list(values.first()))),
+        ("CAPABILITY", _) => Some(ResponseCode::Capability(capabilities_from_values(values))),
+        ("PERMANENTFLAGS", _) => Some(ResponseCode::PermanentFlags(parse_flags(values.first()))),
+        ("UIDNEXT", Some(uid_next)) => Some(ResponseCode::UidNext(uid_next)),
+        ("UIDVALIDITY", Some(uid_validity)) => Some(ResponseCode::UidValidity(uid_validity)),
+        ("UNSEEN", Some(unseen)) => Some(ResponseCode::Unseen(unseen)),
+        ("APPENDUID", Some(uid_validity)) => {
+            values.get(1).and_then(Value::as_number).map(|uid| ResponseCode::AppendUid(uid_validity, uid))
+        },
+        ("COPYUID", Some(uid_validity)) => {
+            let source = values.get(1).and_then(Value::as_atom).and_then(uid_list);
+            let destination = values.get(2).and_then(Value::as_atom).and_then(uid_list);
+            match (source, destination) {
+                (Some(source), Some(destination)) if source.len() == destination.len() => {
+                    Some(ResponseCode::CopyUid(uid_validity, source.into_iter().zip(destination.into_iter()).collect()))
+                },
+                _ => None
+            }
+        },
+        ("HIGHESTMODSEQ", _) => values.first().and_then(Value::as_u64).map(ResponseCode::HighestModSeq),
+        ("MODIFIED", _) => {
+            values.first().and_then(Value::as_atom).and_then(|set| set.parse().ok()).map(ResponseCode::Modified)
+        },
+        _ => None
+    }
+}
 
-    for line in lines.iter() {
-        if exists_regex.is_match(line) {
-            let cap = exists_regex.captures(line).unwrap();
-            mailbox.exists = cap.at(1).unwrap().parse::<u32>().unwrap();
-        } else if recent_regex.is_match(line) {
-            let cap = recent_regex.captures(line).unwrap();
-            mailbox.recent = cap.at(1).unwrap().parse::<u32>().unwrap();
-        } else if flags_regex.is_match(line) {
-            let cap = flags_regex.captures(line).unwrap();
-            mailbox.flags = cap.at(1).unwrap().to_string();
-        } else if unseen_regex.is_match(line) {
-            let cap = unseen_regex.captures(line).unwrap();
-            mailbox.unseen = Some(cap.at(1).unwrap().parse::<u32>().unwrap());
-        } else if uid_validity_regex.is_match(line) {
-            let cap = uid_validity_regex.captures(line).unwrap();
-            mailbox.uid_validity = Some(cap.at(1).unwrap().parse::<u32>().unwrap());
-        } else if uid_next_regex.is_match(line) {
-            let cap = uid_next_regex.captures(line).unwrap();
-            mailbox.uid_next = Some(cap.at(1).unwrap().parse::<u32>().unwrap());
-        } else if permanent_flags_regex.is_match(line) {
-            let cap = permanent_flags_regex.captures(line).unwrap();
-            mailbox.permanent_flags = Some(cap.at(1).unwrap().to_string());
+/// Expands a UID set such as `304,319:320` into its UIDs, keeping the order of the comma
+/// separated items. A range is expanded in ascending order, since `4:2` is the same as `2:4`.
+/// Returns `None` for sets of more than `MAX_COPIED_UIDS` UIDs.
+fn uid_list(set: &str) -> Option<Vec<u32>> {
+    let mut ranges = Vec::new();
+    let mut count = 0u64;
+    for item in set.split(',') {
+        let mut bounds = item.splitn(2, ':');
+        let first = match bounds.next().and_then(|first| first.parse::<u32>().ok()) {
+            Some(first) => first,
+            None => return None
+        };
+        let last = match bounds.next() {
+            Some(last) => match last.parse::<u32>() {
+                Ok(last) => last,
+                Err(_) => return None
+            },
+            None => first
+        };
+        let (low, high) = if first <= last { (first, last) } else { (last, first) };
+        count += (high - low) as u64 + 1;
+        if count > MAX_COPIED_UIDS {
+            return None;
         }
+        ranges.push((low, high));
+    }
+    let mut uids = Vec::with_capacity(count as usize);
+    for (low, high) in ranges {
+        uids.extend((low..high).chain(Some(high)));
     }
+    Some(uids)
+}
 
-    Ok(mailbox)
+/// Returns the atoms and strings in a parenthesized list.
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    value.and_then(Value::as_list).unwrap_or(&[]).iter().filter_map(Value::as_string).collect()
+}
+
+fn parse_flags(value: Option<&Value>) -> Vec<Flag> {
+    value.and_then(Value::as_list).unwrap_or(&[]).iter().filter_map(Value::as_atom).map(Flag::from).collect()
+}
+
+/// A recursive descent parser for the response grammar of RFC 3501, section 9.
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Set when the data ends right after the announcement of a literal.
+    literal_needed: Option<usize>
+}
+
+impl<'a> Parser<'a> {
+    fn response(&mut self) -> ParseResult<Response> {
+        let response = match self.peek() {
+            Some(b'+') => {
+                self.pos += 1;
+                self.skip(SP);
+                Response::Continue(self.text())
+            },
+            Some(b'*') => {
+                self.pos += 1;
+                try!(self.expect(SP));
+                try!(self.untagged())
+            },
+            _ => {
+                let tag = try!(self.word());
+                try!(self.expect(SP));
+                let name = try!(self.word()).to_uppercase();
+                try!(self.status_response(Some(tag), &name))
+            }
+        };
+        try!(self.expect(CR));
+        try!(self.expect(LF));
+        if self.pos != self.data.len() {
+            return Err(());
+        }
+        Ok(response)
+    }
+
+    fn untagged(&mut self) -> ParseResult<Response> {
+        let word = try!(self.word());
+        if let Ok(number) = word.parse::<u32>() {
+            try!(self.expect(SP));
+            let name = try!(self.word()).to_uppercase();
+            let values = try!(self.data_values(&name));
+            return Ok(Response::Data { number: Some(number), name: name, values: values });
+        }
+
+        let name = word.to_uppercase();
+        match &name[..] {
+            "OK" | "NO" | "BAD" | "PREAUTH" | "BYE" => self.status_response(None, &name),
+            _ => {
+                let values = try!(self.data_values(&name));
+                Ok(Response::Data { number: None, name: name, values: values })
+            }
+        }
+    }
+
+    /// Parses the values of untagged data. Data the client does not know about may not follow
+    /// the usual grammar, so if its values cannot be parsed the rest of the line is kept as a
+    /// single string instead.
+    fn data_values(&mut self, name: &str) -> ParseResult<Vec<Value>> {
+        let start = self.pos;
+        match self.values() {
+            Ok(values) => if self.peek() == Some(CR) || self.peek().is_none() {
+                return Ok(values);
+            },
+            Err(()) => if self.literal_needed.is_some() {
+                return Err(());
+            }
+        }
+        if KNOWN_DATA.contains(&name) {
+            return Err(());
+        }
+        self.pos = start;
+        self.skip(SP);
+        Ok(vec![Value::String(self.raw_text().to_vec())])
+    }
+
+    fn status_response(&mut self, tag: Option<String>, name: &str) -> ParseResult<Response> {
+        let status = match name {
+            "OK" => Status::Ok,
+            "NO" => Status::No,
+            "BAD" => Status::Bad,
+            "PREAUTH" => Status::PreAuth,
+            "BYE" => Status::Bye,
+            _ => return Err(())
+        };
+        self.skip(SP);
+        let code = if self.peek() == Some(b'[') {
+            self.pos += 1;
+            let name = try!(self.word()).to_uppercase();
+            self.skip(SP);
+            let start = self.pos;
+            while let Some(c) = self.peek() {
+                if c == b']' || c == CR || c == LF {
+                    break;
+                }
+                self.pos += 1;
+            }
+            let arguments = &self.data[start..self.pos];
+            try!(self.expect(b']'));
+            self.skip(SP);
+            Some(response_code(name, arguments))
+        } else {
+            None
+        };
+        Ok(Response::Status { tag: tag, status: status, code: code, information: self.text() })
+    }
+
+    /// Parses space separated values up to the end of the line or the end of a parenthesized
+    /// list.
+    fn values(&mut self) -> ParseResult<Vec<Value>> {
+        let mut values = Vec::new();
+        loop {
+            self.skip(SP);
+            match self.peek() {
+                None | Some(CR) | Some(b')') => return Ok(values),
+                _ => values.push(try!(self.value()))
+            }
+        }
+    }
+
+    fn value(&mut self) -> ParseResult<Value> {
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                let values = try!(self.values());
+                try!(self.expect(b')'));
+                Ok(Value::List(values))
+            },
+            Some(b'"') => self.quoted().map(Value::String),
+            Some(b'{') => self.literal().map(Value::String),
+            _ => {
+                let atom = try!(self.atom());
+                if atom.eq_ignore_ascii_case("NIL") {
+                    Ok(Value::Nil)
+                } else {
+                    Ok(Value::Atom(atom))
+                }
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> ParseResult<Vec<u8>> {
+        try!(self.expect(b'"'));
+        let mut string = Vec::new();
+        loop {
+            match self.next() {
+                Some(b'"') => return Ok(string),
+                Some(b'\\') => match self.next() {
+                    Some(c) => string.push(c),
+                    None => return Err(())
+                },
+                Some(CR) | Some(LF) | None => return Err(()),
+                Some(c) => string.push(c)
+            }
+        }
+    }
+
+    fn literal(&mut self) -> ParseResult<Vec<u8>> {
+        try!(self.expect(b'{'));
+        let start = self.pos;
+        while let Some(b'0'...b'9') = self.peek() {
+            self.pos += 1;
+        }
+        let size = match str::from_utf8(&self.data[start..self.pos]).ok().and_then(|s| s.parse::<usize>().ok()) {
+            Some(size) => size,
+            None => return Err(())
+        };
+        try!(self.expect(b'}'));
+        try!(self.expect(CR));
+        try!(self.expect(LF));
+        if self.pos == self.data.len() {
+            self.literal_needed = Some(size);
+            return Err(());
+        }
+        if self.data.len() - self.pos < size {
+            return Err(());
+        }
+        let literal = self.data[self.pos..self.pos + size].to_vec();
+        self.pos += size;
+        Ok(literal)
+    }
+
+    /// Parses an atom. A bracketed part, as in `BODY[HEADER.FIELDS (FROM)]<0>`, is kept as part of
+    /// the atom.
+    fn atom(&mut self) -> ParseResult<String> {
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                None | Some(SP) | Some(CR) | Some(LF) | Some(b'(') | Some(b')') | Some(b'"') | Some(b'{') => break,
+                Some(b'[') => {
+                    match self.data[self.pos..].iter().position(|&c| c == b']') {
+                        Some(end) => self.pos += end + 1,
+                        None => return Err(())
+                    }
+                },
+                Some(_) => self.pos += 1
+            }
+        }
+        if self.pos == start {
+            return Err(());
+        }
+        Ok(String::from_utf8_lossy(&self.data[start..self.pos]).into_owned())
+    }
+
+    /// Parses a tag or a keyword, which end at the next space.
+    fn word(&mut self) -> ParseResult<String> {
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                None | Some(SP) | Some(CR) | Some(LF) | Some(b'[') | Some(b']') => break,
+                Some(_) => self.pos += 1
+            }
+        }
+        if self.pos == start {
+            return Err(());
+        }
+        Ok(String::from_utf8_lossy(&self.data[start..self.pos]).into_owned())
+    }
+
+    /// Returns the human-readable text up to the end of the line.
+    fn text(&mut self) -> String {
+        String::from_utf8_lossy(self.raw_text()).into_owned()
+    }
+
+    fn raw_text(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        let end = if self.data.ends_with(&[CR, LF]) { self.data.len() - 2 } else { self.data.len() };
+        self.pos = if end > start { end } else { start };
+        &self.data[start..self.pos]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn skip(&mut self, c: u8) {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> ParseResult<()> {
+        if self.next() == Some(c) {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::body_structure::BodyStructure;
+    use super::super::capabilities::Capabilities;
+    use super::super::response::{Response, ResponseCode, Status, Value};
 
     #[test]
     fn parse_capability_test() {
-        let expected_capabilities = vec![String::from("IMAP4rev1"), String::from("STARTTLS"), String::from("AUTH=GSSAPI"), String::from("LOGINDISABLED")];
-        let lines = vec![String::from("* CAPABILITY IMAP4rev1 STARTTLS AUTH=GSSAPI LOGINDISABLED\r\n"), String::from("a1 OK CAPABILITY completed\r\n")];
-        let capabilities = parse_capability(lines).unwrap();
+        let expected_capabilities = Capabilities::new(vec![String::from("IMAP4rev1"), String::from("STARTTLS"), String::from("AUTH=GSSAPI"), String::from("LOGINDISABLED")]);
+        let responses = vec![parse_response(b"* CAPABILITY IMAP4rev1 STARTTLS AUTH=GSSAPI LOGINDISABLED\r\n").unwrap()];
+        let capabilities = parse_capability(&responses).unwrap();
         assert!(capabilities == expected_capabilities, "Unexpected capabilities parse response");
     }
 
     #[test]
     #[should_panic]
     fn parse_capability_invalid_test() {
-        let lines = vec![String::from("* JUNK IMAP4rev1 STARTTLS AUTH=GSSAPI LOGINDISABLED\r\n"), String::from("a1 OK CAPABILITY completed\r\n")];
-        parse_capability(lines).unwrap();
+        let responses = vec![parse_response(b"* JUNK IMAP4rev1 STARTTLS AUTH=GSSAPI LOGINDISABLED\r\n").unwrap()];
+        parse_capability(&responses).unwrap();
     }
 
     #[test]
     fn parse_response_test() {
-        let lines = vec![String::from("* LIST (\\HasNoChildren) \".\" \"INBOX\"\r\n"), String::from("a2 OK List completed.\r\n")];
-        let expected_lines = lines.clone();
-        let actual_lines = parse_response(lines).unwrap();
-        assert!(expected_lines == actual_lines, "Unexpected parse response");
+        let response = parse_response(b"* LIST (\\HasNoChildren) \".\" \"INBOX\"\r\n").unwrap();
+        let expected_response = Response::Data {
+            number: None,
+            name: String::from("LIST"),
+            values: vec![
+                Value::List(vec![Value::Atom(String::from("\\HasNoChildren"))]),
+                Value::String(b".".to_vec()),
+                Value::String(b"INBOX".to_vec())
+            ]
+        };
+        assert!(response == expected_response, "Unexpected parse response");
+    }
+
+    #[test]
+    fn parse_unknown_response_test() {
+        let response = parse_response(b"* XFOO some \"text\r\n").unwrap();
+        let expected_response = Response::Data {
+            number: None,
+            name: String::from("XFOO"),
+            values: vec![Value::String(b"some \"text".to_vec())]
+        };
+        assert!(response == expected_response, "Unknown response should be kept as text");
+        let response = parse_response(b"* 3 XFOO a) b\r\n").unwrap();
+        assert!(response.data("XFOO") == Some(&[Value::String(b"a) b".to_vec())][..]), "Unknown response should be kept as text");
+        assert!(parse_response(b"* LIST (\\Noselect \"/\" foo\r\n").is_err(), "Known response should be parsed");
+        assert!(literal_needed(b"* XFOO {3}\r\n") == Some(3), "Literal should be read");
+    }
+
+    #[test]
+    fn parse_tagged_response_test() {
+        let response = parse_response(b"a2 OK [READ-ONLY] Select completed.\r\n").unwrap();
+        let expected_response = Response::Status {
+            tag: Some(String::from("a2")),
+            status: Status::Ok,
+            code: Some(ResponseCode::ReadOnly),
+            information: String::from("Select completed.")
+        };
+        assert!(response == expected_response, "Unexpected parse response");
+    }
+
+    #[test]
+    fn parse_response_code_test() {
+        let codes = vec![
+            (&b"* OK [PERMANENTFLAGS (\\Deleted \\Seen \\*)] Limited\r\n"[..],
+                ResponseCode::PermanentFlags(vec![Flag::Deleted, Flag::Seen, Flag::MayCreate])),
+            (&b"* OK [UIDNEXT 4392] Predicted next UID\r\n"[..], ResponseCode::UidNext(4392)),
+            (&b"a1 OK [COPYUID 38505 304,319:320 3956:3958] Done\r\n"[..],
+                ResponseCode::CopyUid(38505, vec![(304, 3956), (319, 3957), (320, 3958)])),
+            (&b"a1 OK [COPYUID 38505 3,1:2 9:8,10] Done\r\n"[..], ResponseCode::CopyUid(38505, vec![(3, 8), (1, 9), (2, 10)])),
+            (&b"a1 OK [COPYUID 1 1:4294967295 1:4294967295] Done\r\n"[..],
+                ResponseCode::Other(String::from("COPYUID"), Some(String::from("1 1:4294967295 1:4294967295")))),
+            (&b"a1 NO [BADCHARSET (UTF-8 \"US-ASCII\")] Unsupported\r\n"[..],
+                ResponseCode::BadCharset(vec![String::from("UTF-8"), String::from("US-ASCII")])),
+            (&b"a1 NO [TRYCREATE] No such mailbox\r\n"[..], ResponseCode::TryCreate),
+            (&b"* OK [HIGHESTMODSEQ 715194045007] Highest\r\n"[..], ResponseCode::HighestModSeq(715194045007)),
+            (&b"a1 OK [MODIFIED 7,9] Conditional STORE failed\r\n"[..], ResponseCode::Modified("7,9".parse().unwrap())),
+            (&b"a1 NO [AUTHENTICATIONFAILED] Authentication failed.\r\n"[..], ResponseCode::AuthenticationFailed),
+            (&b"* OK [X-GOOGLE 12] Other\r\n"[..],
+                ResponseCode::Other(String::from("X-GOOGLE"), Some(String::from("12")))),
+            (&b"a1 NO [X-ERR text (unbalanced] failed\r\n"[..],
+                ResponseCode::Other(String::from("X-ERR"), Some(String::from("text (unbalanced")))),
+            (&b"* OK [UIDNEXT soon] Invalid\r\n"[..], ResponseCode::Other(String::from("UIDNEXT"), Some(String::from("soon")))),
+            (&b"* OK [X-NONE] Other\r\n"[..], ResponseCode::Other(String::from("X-NONE"), None))
+        ];
+        for (data, expected_code) in codes {
+            let response = parse_response(data).unwrap();
+            assert!(response.code() == Some(&expected_code), "Unexpected response code {:?}", response.code());
+        }
+    }
+
+    #[test]
+    fn parse_fetch_response_test() {
+        let response = parse_response(b"* 12 FETCH (UID 44 FLAGS (\\Seen) BODY[HEADER.FIELDS (FROM)] {5}\r\nFrom: \
+            ENVELOPE (NIL \"a \\\"b\\\"\"))\r\n").unwrap();
+        let expected_response = Response::Data {
+            number: Some(12),
+            name: String::from("FETCH"),
+            values: vec![Value::List(vec![
+                Value::Atom(String::from("UID")),
+                Value::Atom(String::from("44")),
+                Value::Atom(String::from("FLAGS")),
+                Value::List(vec![Value::Atom(String::from("\\Seen"))]),
+                Value::Atom(String::from("BODY[HEADER.FIELDS (FROM)]")),
+                Value::String(b"From:".to_vec()),
+                Value::Atom(String::from("ENVELOPE")),
+                Value::List(vec![Value::Nil, Value::String(b"a \"b\"".to_vec())])
+            ])]
+        };
+        assert!(response == expected_response, "Unexpected parse response");
+    }
+
+    #[test]
+    fn parse_fetch_test() {
+        let responses = vec![
+            parse_response(b"* 12 FETCH (UID 44 FLAGS (\\Seen \\Answered) INTERNALDATE \"17-Jul-1996 02:44:25 -0700\" \
+                RFC822.SIZE 4286 MODSEQ (12121231000) BODY[HEADER] {9}\r\nSubject: BODY[1.2] \"part\")\r\n").unwrap(),
+            parse_response(b"* 3 EXISTS\r\n").unwrap()
+        ];
+        let fetches = parse_fetches(&responses).unwrap();
+        assert!(fetches.len() == 1, "Unexpected number of fetch results");
+        let fetch = &fetches[0];
+        assert!(fetch.message == 12, "Unexpected message number");
+        assert!(fetch.uid == Some(44), "Unexpected uid");
+        assert!(fetch.flags == vec![Flag::Seen, Flag::Answered], "Unexpected flags");
+        assert!(fetch.internal_date == Some(String::from("17-Jul-1996 02:44:25 -0700")), "Unexpected internal date");
+        assert!(fetch.rfc822_size == Some(4286), "Unexpected size");
+        assert!(fetch.mod_seq == Some(12121231000), "Unexpected mod-sequence");
+        assert!(fetch.header() == Some(&b"Subject: "[..]), "Unexpected header section");
+        assert!(fetch.body("1.2") == Some(&b"part"[..]), "Unexpected body section");
+        assert!(fetch.body("") == None, "Unexpected whole body");
+    }
+
+    #[test]
+    fn parse_vanished_test() {
+        let responses = vec![
+            parse_response(b"* VANISHED (EARLIER) 41,43:116\r\n").unwrap(),
+            parse_response(b"* VANISHED (EARLIER) 118\r\n").unwrap()
+        ];
+        let uids = parse_vanished(&responses).unwrap();
+        assert!(uids.to_string() == "41,43:116,118", "Unexpected vanished UIDs {}", uids);
+        let unsolicited = parse_unsolicited(&parse_response(b"* VANISHED 405\r\n").unwrap()).unwrap();
+        assert!(unsolicited == Some(UnsolicitedResponse::Vanished(SequenceSet::from(405))), "Unexpected update {:?}", unsolicited);
+    }
+
+    #[test]
+    fn parse_namespaces_test() {
+        let responses = vec![
+            parse_response(b"* NAMESPACE ((\"\" \"/\")) NIL ((\"#shared/\" \"/\" \"X-EXT\" (\"A\")) (\"#news.\" NIL))\r\n").unwrap()
+        ];
+        let namespaces = parse_namespaces(&responses).unwrap();
+        assert!(namespaces.personal == vec![Namespace { prefix: String::new(), delimiter: Some(String::from("/")) }],
+            "Unexpected personal namespaces {:?}", namespaces.personal);
+        assert!(namespaces.other_users.is_empty(), "Unexpected other users' namespaces");
+        assert!(namespaces.shared == vec![
+            Namespace { prefix: String::from("#shared/"), delimiter: Some(String::from("/")) },
+            Namespace { prefix: String::from("#news."), delimiter: None }
+        ], "Unexpected shared namespaces {:?}", namespaces.shared);
+        assert!(parse_namespaces(&[parse_response(b"* NAMESPACE NIL\r\n").unwrap()]).is_err(), "Incomplete NAMESPACE should fail");
+    }
+
+    #[test]
+    fn parse_id_test() {
+        let responses = vec![
+            parse_response(b"* ID (\"name\" \"Cyrus\" \"version\" \"1.5\" \"os\" NIL)\r\n").unwrap()
+        ];
+        let id = parse_id(&responses).unwrap();
+        assert!(id.len() == 2, "Unexpected number of ID fields {:?}", id);
+        assert!(id.get("name") == Some(&String::from("Cyrus")) && id.get("version") == Some(&String::from("1.5")),
+            "Unexpected ID fields {:?}", id);
+        assert!(parse_id(&[parse_response(b"* ID NIL\r\n").unwrap()]).unwrap().is_empty(), "NIL should give no fields");
+    }
+
+    #[test]
+    fn parse_envelope_test() {
+        let response = parse_response(b"* 1 FETCH (ENVELOPE (\"Wed, 17 Jul 1996 02:23:25 -0700 (PDT)\" \
+            \"IMAP4rev1 WG mtg summary\" ((\"Terry Gray\" NIL \"gray\" \"cac.washington.edu\")) NIL NIL \
+            ((NIL NIL \"imap\" \"cac.washington.edu\")) \
+            ((NIL NIL \"Team\" NIL)(NIL NIL \"minutes\" \"CNRI.Reston.VA.US\")(NIL NIL NIL NIL)) \
+            NIL NIL \"<B27397-0100000@cac.washington.edu>\"))\r\n").unwrap();
+        let fetches = parse_fetches(&[response]).unwrap();
+        let envelope = fetches[0].envelope.as_ref().unwrap();
+        assert!(envelope.subject == Some(String::from("IMAP4rev1 WG mtg summary")), "Unexpected subject");
+        assert!(envelope.from.len() == 1, "Unexpected from addresses");
+        assert!(envelope.from[0].name == Some(String::from("Terry Gray")), "Unexpected from name");
+        assert!(envelope.from[0].email() == Some(String::from("gray@cac.washington.edu")), "Unexpected from address");
+        assert!(envelope.sender.is_empty() && envelope.reply_to.is_empty(), "Unexpected sender or reply-to");
+        assert!(envelope.to[0].group == None, "Unexpected group");
+        assert!(envelope.cc.len() == 1, "Group markers should not be returned as addresses");
+        assert!(envelope.cc[0].group == Some(String::from("Team")), "Unexpected group");
+        assert!(envelope.cc[0].mailbox == Some(String::from("minutes")), "Unexpected group member");
+        assert!(envelope.message_id == Some(String::from("<B27397-0100000@cac.washington.edu>")), "Unexpected message id");
+    }
+
+    #[test]
+    fn parse_body_structure_test() {
+        let response = parse_response(b"* 1 FETCH (BODYSTRUCTURE (\
+            (\"TEXT\" \"PLAIN\" (\"CHARSET\" \"US-ASCII\") NIL NIL \"7BIT\" 1152 23)\
+            ((\"TEXT\" \"HTML\" (\"CHARSET\" \"UTF-8\") NIL NIL \"QUOTED-PRINTABLE\" 2000 40 NIL NIL NIL)\
+            (\"IMAGE\" \"PNG\" (\"NAME\" \"logo.png\") \"<logo>\" NIL \"BASE64\" 4554 NIL \
+            (\"inline\" (\"FILENAME\" \"logo.png\")) NIL) \"RELATED\" (\"BOUNDARY\" \"b2\") NIL NIL)\
+            (\"MESSAGE\" \"RFC822\" NIL NIL NIL \"7BIT\" 342 \
+            (NIL \"Fwd\" NIL NIL NIL NIL NIL NIL NIL NIL) \
+            (\"TEXT\" \"PLAIN\" NIL NIL NIL \"7BIT\" 20 1) 9)\
+            \"MIXED\" (\"BOUNDARY\" \"b1\") NIL NIL))\r\n").unwrap();
+        let fetches = parse_fetches(&[response]).unwrap();
+        let body_structure = fetches[0].body_structure.as_ref().unwrap();
+        assert!(body_structure.content_type() == "multipart/mixed", "Unexpected content type");
+
+        let parts: Vec<(String, String)> = body_structure.parts().into_iter()
+            .map(|(number, part)| (number, part.content_type())).collect();
+        let expected_parts = vec![
+            (String::from("1"), String::from("text/plain")),
+            (String::from("2.1"), String::from("text/html")),
+            (String::from("2.2"), String::from("image/png")),
+            (String::from("3"), String::from("message/rfc822")),
+            (String::from("3.1"), String::from("text/plain"))
+        ];
+        assert!(parts == expected_parts, "Unexpected part numbering");
+
+        match *body_structure.part("1").unwrap() {
+            BodyStructure::Text { ref part, lines } => {
+                assert!(lines == 23 && part.size == 1152, "Unexpected text part size");
+                assert!(part.params == vec![(String::from("CHARSET"), String::from("US-ASCII"))], "Unexpected params");
+            },
+            ref part => panic!("Unexpected part {:?}", part)
+        }
+        let image = body_structure.part("2.2").unwrap().single_part().unwrap();
+        assert!(image.encoding == "BASE64" && image.id == Some(String::from("<logo>")), "Unexpected image part");
+        let disposition = image.disposition.as_ref().unwrap();
+        assert!(disposition.kind == "inline", "Unexpected disposition");
+        assert!(disposition.params == vec![(String::from("FILENAME"), String::from("logo.png"))], "Unexpected disposition params");
+        match *body_structure.part("3").unwrap() {
+            BodyStructure::Message { ref envelope, lines, .. } => {
+                assert!(envelope.subject == Some(String::from("Fwd")) && lines == 9, "Unexpected message part");
+            },
+            ref part => panic!("Unexpected part {:?}", part)
+        }
+    }
+
+    #[test]
+    fn literal_needed_test() {
+        assert!(literal_needed(b"* 1 FETCH (BODY[] {12}\r\n") == Some(12), "Literal should be announced");
+        assert!(literal_needed(b"* 1 FETCH (BODY[] {2}\r\nab BODY[1] {0}\r\n") == Some(0), "Second literal should be announced");
+        assert!(literal_needed(b"* 1 FETCH (BODY[] {2}\r\nab)\r\n") == None, "Complete response has no literal");
+        assert!(literal_needed(b"* OK [ALERT] quota {3}\r\n") == None, "Status text has no literals");
+        assert!(literal_needed(b"a1 NO over {3}\r\n") == None, "Status text has no literals");
+    }
+
+    #[test]
+    fn parse_continue_response_test() {
+        let response = parse_response(b"+ idling\r\n").unwrap();
+        assert!(response == Response::Continue(String::from("idling")), "Unexpected parse response");
     }
 
     #[test]
     #[should_panic]
     fn parse_response_invalid_test() {
-        let lines = vec![String::from("* LIST (\\HasNoChildren) \".\" \"INBOX\"\r\n"), String::from("a2 BAD broken.\r\n")];
-        parse_response(lines).unwrap();
+        parse_response(b"* LIST (\\HasNoChildren \".\" \"INBOX\"\r\n").unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn check_response_invalid_test() {
+        let responses = vec![
+            parse_response(b"* LIST (\\HasNoChildren) \".\" \"INBOX\"\r\n").unwrap(),
+            parse_response(b"a2 BAD broken.\r\n").unwrap()
+        ];
+        check_response(responses).unwrap();
     }
 }
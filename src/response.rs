@@ -0,0 +1,232 @@
+use std::fmt;
+
+use super::capabilities::Capabilities;
+use super::fetch::Fetch;
+use super::flag::Flag;
+use super::sequence_set::SequenceSet;
+
+/// A single response sent by the IMAP server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// A command continuation request (`+ ...`), carrying the text sent along with it.
+    Continue(String),
+    /// A status response (OK, NO, BAD, PREAUTH or BYE). Untagged status responses have no tag.
+    Status {
+        tag: Option<String>,
+        status: Status,
+        code: Option<ResponseCode>,
+        information: String
+    },
+    /// Untagged server data such as `* 3 EXISTS` or `* LIST (\Noselect) "/" foo`.
+    Data {
+        number: Option<u32>,
+        name: String,
+        values: Vec<Value>
+    }
+}
+
+impl Response {
+    /// Returns the data values if this is untagged data with the given (upper case) name.
+    pub fn data(&self, data_name: &str) -> Option<&[Value]> {
+        match *self {
+            Response::Data { ref name, ref values, .. } if name == data_name => Some(values),
+            _ => None
+        }
+    }
+
+    /// Returns the message number if this is numbered data (e.g. EXISTS or FETCH) with the given
+    /// (upper case) name.
+    pub fn numbered_data(&self, data_name: &str) -> Option<(u32, &[Value])> {
+        match *self {
+            Response::Data { number: Some(number), ref name, ref values } if name == data_name => {
+                Some((number, values))
+            },
+            _ => None
+        }
+    }
+
+    /// Returns the response code if this is a status response carrying one.
+    pub fn code(&self) -> Option<&ResponseCode> {
+        match *self {
+            Response::Status { ref code, .. } => code.as_ref(),
+            _ => None
+        }
+    }
+}
+
+/// A mailbox update that the server sent without being asked for it, e.g. because another
+/// client changed the mailbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnsolicitedResponse {
+    /// The number of messages in the mailbox changed (`* 5 EXISTS`).
+    Exists(u32),
+    /// The number of messages with the `\Recent` flag changed (`* 1 RECENT`).
+    Recent(u32),
+    /// The message with this sequence number was expunged (`* 3 EXPUNGE`). The sequence numbers
+    /// of all later messages decrease by one.
+    Expunge(u32),
+    /// Message data changed, usually its flags (`* 4 FETCH (FLAGS (\Seen))`).
+    Fetch(Fetch),
+    /// The messages with these UIDs were expunged (`* VANISHED 41,43:116`). Sent instead of
+    /// EXPUNGE once QRESYNC is enabled (RFC 7162).
+    Vanished(SequenceSet)
+}
+
+/// The status condition of a status response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    No,
+    Bad,
+    PreAuth,
+    Bye
+}
+
+/// A response code sent in brackets at the beginning of a status response, such as
+/// `[UIDNEXT 4392]`. Besides the codes of RFC 3501, the codes of RFC 5530 are recognized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseCode {
+    /// A message the client must present to the user.
+    Alert,
+    /// The charset of a SEARCH is not supported, with the supported charsets if sent.
+    BadCharset(Vec<String>),
+    Capability(Capabilities),
+    /// A message could not be parsed by the server.
+    Parse,
+    PermanentFlags(Vec<Flag>),
+    ReadOnly,
+    ReadWrite,
+    /// The target mailbox of an APPEND or COPY does not exist but can be created.
+    TryCreate,
+    UidNext(u32),
+    UidValidity(u32),
+    Unseen(u32),
+    Unavailable,
+    AuthenticationFailed,
+    AuthorizationFailed,
+    Expired,
+    PrivacyRequired,
+    ContactAdmin,
+    NoPerm,
+    InUse,
+    ExpungeIssued,
+    Corruption,
+    ServerBug,
+    ClientBug,
+    Cannot,
+    Limit,
+    OverQuota,
+    AlreadyExists,
+    NonExistent,
+    /// The UID validity of the target mailbox and the UID of a message added with APPEND, sent
+    /// by servers with the UIDPLUS capability (RFC 4315).
+    AppendUid(u32, u32),
+    /// The UID validity of the target mailbox and, for each copied message, its UID and its UID
+    /// in the target mailbox, in the order of the UID sets sent by the server with each range in
+    /// ascending order. Sent after COPY or MOVE by servers with UIDPLUS.
+    CopyUid(u32, Vec<(u32, u32)>),
+    /// The highest mod-sequence value of the selected mailbox, sent by servers with CONDSTORE
+    /// (RFC 7162).
+    HighestModSeq(u64),
+    /// The selected mailbox does not support mod-sequences.
+    NoModSeq,
+    /// The messages of a STORE with UNCHANGEDSINCE that were not changed because they were
+    /// modified since.
+    Modified(SequenceSet),
+    /// Any other response code, with its upper case name and the raw text of its arguments.
+    Other(String, Option<String>)
+}
+
+/// A value in server data, as defined by the IMAP formal syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// An atom or number, such as `\Seen`, `UID` or `42`.
+    Atom(String),
+    /// A quoted string or a literal.
+    String(Vec<u8>),
+    /// The special value NIL.
+    Nil,
+    /// A parenthesized list.
+    List(Vec<Value>)
+}
+
+impl Value {
+    /// Returns the atom, if this is one.
+    pub fn as_atom(&self) -> Option<&str> {
+        match *self {
+            Value::Atom(ref atom) => Some(atom),
+            _ => None
+        }
+    }
+
+    /// Returns the number, if this is an atom consisting only of digits.
+    pub fn as_number(&self) -> Option<u32> {
+        self.as_atom().and_then(|atom| atom.parse::<u32>().ok())
+    }
+
+    /// Returns the number, if this is an atom consisting only of digits and fits in 64 bits, such
+    /// as a mod-sequence value.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_atom().and_then(|atom| atom.parse::<u64>().ok())
+    }
+
+    /// Returns the raw contents of a string, if this is one.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Value::String(ref bytes) => Some(bytes),
+            _ => None
+        }
+    }
+
+    /// Returns the value of an atom or a string (an `astring` in the formal syntax).
+    pub fn as_string(&self) -> Option<String> {
+        match *self {
+            Value::Atom(ref atom) => Some(atom.clone()),
+            Value::String(ref bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            _ => None
+        }
+    }
+
+    /// Returns the string contents, treating NIL as absent (an `nstring` in the formal syntax).
+    pub fn as_nstring(&self) -> Option<String> {
+        match *self {
+            Value::Nil => None,
+            ref value => value.as_string()
+        }
+    }
+
+    /// Returns the elements, if this is a parenthesized list.
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match *self {
+            Value::List(ref values) => Some(values),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Atom(ref atom) => f.write_str(atom),
+            Value::String(ref bytes) => {
+                let string = String::from_utf8_lossy(bytes);
+                if string.contains('\r') || string.contains('\n') {
+                    write!(f, "{{{}}}\r\n{}", bytes.len(), string)
+                } else {
+                    write!(f, "\"{}\"", string.replace("\\", "\\\\").replace("\"", "\\\""))
+                }
+            },
+            Value::Nil => f.write_str("NIL"),
+            Value::List(ref values) => {
+                try!(f.write_str("("));
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        try!(f.write_str(" "));
+                    }
+                    try!(fmt::Display::fmt(value, f));
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
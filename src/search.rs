@@ -0,0 +1,357 @@
+use std::fmt;
+
+use super::command::{Command, is_atom};
+use super::error::{Error, Result};
+use super::sequence_set::SequenceSet;
+
+static MONTHS: [&'static str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// A date used in search criteria such as SINCE and BEFORE, sent as e.g. `1-Feb-1994`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8
+}
+
+impl Date {
+    /// Creates a date, or returns `None` if the month is not in 1..12 or the day not in 1..31.
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Date> {
+        if month < 1 || month > 12 || day < 1 || day > 31 {
+            return None;
+        }
+        Some(Date { year: year, month: month, day: day })
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}-{}", self.day, MONTHS[self.month as usize - 1], self.year)
+    }
+}
+
+/// A builder for the criteria of the SEARCH command. All criteria added to a query must match;
+/// use `or` and `not` to combine queries differently. An empty query matches all messages.
+///
+/// ```no_run
+/// # use imap::search::{Date, SearchQuery};
+/// let query = SearchQuery::new()
+///     .unseen()
+///     .since(Date::new(2016, 7, 1).unwrap())
+///     .or(SearchQuery::new().from("alice@example.com"), SearchQuery::new().subject("invoice"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SearchQuery {
+    criteria: Vec<Command>,
+    charset: Option<String>,
+    invalid_keyword: Option<String>
+}
+
+impl SearchQuery {
+    pub fn new() -> SearchQuery {
+        SearchQuery::default()
+    }
+
+    /// Sets the charset of the strings in the query. If it is not set and a string contains
+    /// non-ASCII characters, `UTF-8` is used.
+    pub fn charset(mut self, charset: &str) -> SearchQuery {
+        self.charset = Some(charset.to_string());
+        self
+    }
+
+    pub fn all(self) -> SearchQuery {
+        self.key("ALL")
+    }
+
+    pub fn answered(self) -> SearchQuery {
+        self.key("ANSWERED")
+    }
+
+    pub fn unanswered(self) -> SearchQuery {
+        self.key("UNANSWERED")
+    }
+
+    pub fn deleted(self) -> SearchQuery {
+        self.key("DELETED")
+    }
+
+    pub fn undeleted(self) -> SearchQuery {
+        self.key("UNDELETED")
+    }
+
+    pub fn draft(self) -> SearchQuery {
+        self.key("DRAFT")
+    }
+
+    pub fn undraft(self) -> SearchQuery {
+        self.key("UNDRAFT")
+    }
+
+    pub fn flagged(self) -> SearchQuery {
+        self.key("FLAGGED")
+    }
+
+    pub fn unflagged(self) -> SearchQuery {
+        self.key("UNFLAGGED")
+    }
+
+    pub fn recent(self) -> SearchQuery {
+        self.key("RECENT")
+    }
+
+    pub fn seen(self) -> SearchQuery {
+        self.key("SEEN")
+    }
+
+    pub fn unseen(self) -> SearchQuery {
+        self.key("UNSEEN")
+    }
+
+    /// Matches messages with the given keyword flag set. The keyword must be an atom.
+    pub fn keyword(self, keyword: &str) -> SearchQuery {
+        self.keyword_key("KEYWORD", keyword)
+    }
+
+    /// Matches messages without the given keyword flag set. The keyword must be an atom.
+    pub fn unkeyword(self, keyword: &str) -> SearchQuery {
+        self.keyword_key("UNKEYWORD", keyword)
+    }
+
+    pub fn from(self, value: &str) -> SearchQuery {
+        self.string_key("FROM", value)
+    }
+
+    pub fn to(self, value: &str) -> SearchQuery {
+        self.string_key("TO", value)
+    }
+
+    pub fn cc(self, value: &str) -> SearchQuery {
+        self.string_key("CC", value)
+    }
+
+    pub fn bcc(self, value: &str) -> SearchQuery {
+        self.string_key("BCC", value)
+    }
+
+    pub fn subject(self, value: &str) -> SearchQuery {
+        self.string_key("SUBJECT", value)
+    }
+
+    pub fn body(self, value: &str) -> SearchQuery {
+        self.string_key("BODY", value)
+    }
+
+    /// Matches messages that contain the string in the header or body.
+    pub fn text(self, value: &str) -> SearchQuery {
+        self.string_key("TEXT", value)
+    }
+
+    /// Matches messages with a header field of the given name that contains the value. An empty
+    /// value matches all messages that have the header field.
+    pub fn header(mut self, name: &str, value: &str) -> SearchQuery {
+        self.detect_charset(name);
+        self.detect_charset(value);
+        self.criterion(Command::new("HEADER").quoted(name).quoted(value))
+    }
+
+    /// Matches messages whose internal date is on or after the date.
+    pub fn since(self, date: Date) -> SearchQuery {
+        self.key(&format!("SINCE {}", date))
+    }
+
+    /// Matches messages whose internal date is before the date.
+    pub fn before(self, date: Date) -> SearchQuery {
+        self.key(&format!("BEFORE {}", date))
+    }
+
+    /// Matches messages whose internal date is on the date.
+    pub fn on(self, date: Date) -> SearchQuery {
+        self.key(&format!("ON {}", date))
+    }
+
+    /// Matches messages whose Date header is on or after the date.
+    pub fn sent_since(self, date: Date) -> SearchQuery {
+        self.key(&format!("SENTSINCE {}", date))
+    }
+
+    /// Matches messages whose Date header is before the date.
+    pub fn sent_before(self, date: Date) -> SearchQuery {
+        self.key(&format!("SENTBEFORE {}", date))
+    }
+
+    /// Matches messages whose Date header is on the date.
+    pub fn sent_on(self, date: Date) -> SearchQuery {
+        self.key(&format!("SENTON {}", date))
+    }
+
+    /// Matches messages larger than the given number of octets.
+    pub fn larger(self, size: u32) -> SearchQuery {
+        self.key(&format!("LARGER {}", size))
+    }
+
+    /// Matches messages smaller than the given number of octets.
+    pub fn smaller(self, size: u32) -> SearchQuery {
+        self.key(&format!("SMALLER {}", size))
+    }
+
+    /// Matches messages with the given sequence numbers. An empty set matches no messages.
+    pub fn sequence_set<S: Into<SequenceSet>>(self, sequence_set: S) -> SearchQuery {
+        let sequence_set = sequence_set.into();
+        if sequence_set.is_empty() {
+            return self.key("NOT ALL");
+        }
+        self.key(&sequence_set.to_string())
+    }
+
+    /// Matches messages with the given unique identifiers. An empty set matches no messages.
+    pub fn uid<S: Into<SequenceSet>>(self, uid_set: S) -> SearchQuery {
+        let uid_set = uid_set.into();
+        if uid_set.is_empty() {
+            return self.key("NOT ALL");
+        }
+        self.key(&format!("UID {}", uid_set))
+    }
+
+    /// Matches messages that match either of the queries.
+    pub fn or(mut self, first: SearchQuery, second: SearchQuery) -> SearchQuery {
+        let criterion = Command::new("OR").command(self.nested(first)).command(self.nested(second));
+        self.criterion(criterion)
+    }
+
+    /// Matches messages that do not match the query.
+    pub fn not(mut self, query: SearchQuery) -> SearchQuery {
+        let criterion = Command::new("NOT").command(self.nested(query));
+        self.criterion(criterion)
+    }
+
+    /// Returns the arguments of the SEARCH command, including the CHARSET specification. Strings
+    /// that cannot be quoted are sent as literals. Fails if a keyword is not an atom.
+    pub fn to_command(&self) -> Result<Command> {
+        if let Some(ref keyword) = self.invalid_keyword {
+            return Err(Error::InvalidArgument(format!("Invalid keyword {:?}", keyword)));
+        }
+        let mut command = match self.charset {
+            Some(ref charset) => Command::new("CHARSET").string(charset),
+            None => Command::default()
+        };
+        if self.criteria.is_empty() {
+            return Ok(command.raw("ALL"));
+        }
+        for criterion in self.criteria.iter() {
+            command = command.command(criterion.clone());
+        }
+        Ok(command)
+    }
+
+    /// Returns the arguments of the SEARCH command as text, with any literals inline.
+    pub fn to_arguments(&self) -> Result<String> {
+        self.to_command().map(|command| command.to_string())
+    }
+
+    fn key(self, criterion: &str) -> SearchQuery {
+        self.criterion(Command::new(criterion))
+    }
+
+    fn criterion(mut self, criterion: Command) -> SearchQuery {
+        self.criteria.push(criterion);
+        self
+    }
+
+    fn string_key(mut self, name: &str, value: &str) -> SearchQuery {
+        self.detect_charset(value);
+        self.criterion(Command::new(name).quoted(value))
+    }
+
+    fn keyword_key(mut self, name: &str, keyword: &str) -> SearchQuery {
+        if !is_atom(keyword) && self.invalid_keyword.is_none() {
+            self.invalid_keyword = Some(keyword.to_string());
+        }
+        self.criterion(Command::new(name).raw(keyword))
+    }
+
+    /// Switches the query to UTF-8 if the string contains non-ASCII characters.
+    fn detect_charset(&mut self, value: &str) {
+        if self.charset.is_none() && value.bytes().any(|b| b >= 0x80) {
+            self.charset = Some(String::from("UTF-8"));
+        }
+    }
+
+    /// Returns a query as a single search key, taking over its charset and invalid keyword.
+    fn nested(&mut self, mut query: SearchQuery) -> Command {
+        if self.charset.is_none() {
+            self.charset = query.charset;
+        }
+        if self.invalid_keyword.is_none() {
+            self.invalid_keyword = query.invalid_keyword;
+        }
+        match query.criteria.len() {
+            0 => Command::new("ALL"),
+            1 => query.criteria.remove(0),
+            _ => Command::default().group(query.criteria)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date() {
+        assert!(Date::new(1994, 2, 1).unwrap().to_string() == "1-Feb-1994", "Unexpected date format");
+        assert!(Date::new(1994, 13, 1).is_none(), "Invalid month should be rejected");
+    }
+
+    #[test]
+    fn empty_query() {
+        assert!(SearchQuery::new().to_arguments().unwrap() == "ALL", "Empty query should match all messages");
+    }
+
+    #[test]
+    fn query() {
+        let query = SearchQuery::new()
+            .unseen()
+            .since(Date::new(2016, 7, 1).unwrap())
+            .from("\"Smith\" <smith@example.com>")
+            .larger(1000)
+            .header("X-Mailer", "")
+            .uid(1..101);
+        assert!(query.to_arguments().unwrap() == "UNSEEN SINCE 1-Jul-2016 FROM \"\\\"Smith\\\" <smith@example.com>\" \
+            LARGER 1000 HEADER \"X-Mailer\" \"\" UID 1:100", "Unexpected query {:?}", query.to_arguments());
+    }
+
+    #[test]
+    fn empty_sets() {
+        let query = SearchQuery::new().uid(SequenceSet::new());
+        assert!(query.to_arguments().unwrap() == "NOT ALL", "Empty UID set should match nothing, got {:?}", query.to_arguments());
+        let query = SearchQuery::new().not(SearchQuery::new().sequence_set(Vec::new()));
+        assert!(query.to_arguments().unwrap() == "NOT NOT ALL", "Unexpected query {:?}", query.to_arguments());
+    }
+
+    #[test]
+    fn combinators() {
+        let query = SearchQuery::new()
+            .or(SearchQuery::new().from("alice"), SearchQuery::new().to("bob").seen())
+            .not(SearchQuery::new().keyword("$Junk"));
+        assert!(query.to_arguments().unwrap() == "OR FROM \"alice\" (TO \"bob\" SEEN) NOT KEYWORD $Junk",
+            "Unexpected query {:?}", query.to_arguments());
+    }
+
+    #[test]
+    fn charset() {
+        let query = SearchQuery::new().subject("Grüße");
+        assert!(query.to_arguments().unwrap() == "CHARSET UTF-8 SUBJECT {7}\r\nGrüße", "Unexpected query {:?}", query.to_arguments());
+        let nested = SearchQuery::new().not(SearchQuery::new().body("日本"));
+        assert!(nested.to_arguments().unwrap().starts_with("CHARSET UTF-8 "), "Nested charset should be used");
+    }
+
+    #[test]
+    fn invalid_keywords() {
+        for keyword in ["", "a b", "x)\r\na2 DELETE INBOX", "\\Seen", "Grüße"].iter() {
+            let query = SearchQuery::new().keyword(keyword);
+            assert!(query.to_command().is_err(), "Keyword {:?} should be rejected", keyword);
+        }
+        let query = SearchQuery::new().or(SearchQuery::new().seen(), SearchQuery::new().unkeyword("a\"b"));
+        assert!(query.to_arguments().is_err(), "Nested invalid keyword should be rejected");
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::{Range, RangeFrom};
+use std::str::FromStr;
+
+/// A set of message sequence numbers or UIDs, such as `1:3,5,7:*`. Adjacent and overlapping
+/// numbers are compressed into ranges. An empty set has no text form, so the client does not
+/// send commands for empty sets.
+///
+/// ```
+/// # use imap::sequence_set::SequenceSet;
+/// let set: SequenceSet = vec![1, 2, 3, 5, 9, 10].into_iter().collect();
+/// assert_eq!(set.to_string(), "1:3,5,9:10");
+/// assert_eq!(SequenceSet::from(7..).to_string(), "7:*");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SequenceSet {
+    /// Sorted, non-overlapping and non-adjacent inclusive ranges.
+    ranges: Vec<(u32, u32)>,
+    /// The start of a range up to the largest number in use (`n:*`).
+    from: Option<u32>,
+    /// Whether the largest number in use (`*`) is part of the set.
+    largest: bool
+}
+
+impl SequenceSet {
+    pub fn new() -> SequenceSet {
+        SequenceSet::default()
+    }
+
+    /// Returns the set of all messages, `1:*`.
+    pub fn all() -> SequenceSet {
+        SequenceSet::new().with_from(1)
+    }
+
+    /// Returns the set containing only the last message, `*`.
+    pub fn last() -> SequenceSet {
+        let mut set = SequenceSet::new();
+        set.largest = true;
+        set
+    }
+
+    /// Adds a single number.
+    pub fn insert(&mut self, number: u32) {
+        self.insert_range(number, number);
+    }
+
+    /// Adds all numbers from `start` to `end`, inclusive.
+    pub fn insert_range(&mut self, start: u32, end: u32) {
+        let (mut start, mut end) = if start <= end { (start, end) } else { (end, start) };
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 1);
+        for &(s, e) in self.ranges.iter() {
+            if e.saturating_add(1) < start || end.saturating_add(1) < s {
+                ranges.push((s, e));
+            } else {
+                start = if s < start { s } else { start };
+                end = if e > end { e } else { end };
+            }
+        }
+        ranges.push((start, end));
+        ranges.sort();
+        self.ranges = ranges;
+        self.normalize();
+    }
+
+    /// Adds all numbers from `start` up to the largest number in use (`start:*`).
+    pub fn insert_from(&mut self, start: u32) {
+        self.from = Some(match self.from {
+            Some(from) if from < start => from,
+            _ => start
+        });
+        self.normalize();
+    }
+
+    /// Adds the largest number in use (`*`).
+    pub fn insert_last(&mut self) {
+        self.largest = true;
+        self.normalize();
+    }
+
+    /// Adds all numbers of another set.
+    pub fn insert_set(&mut self, other: &SequenceSet) {
+        for &(start, end) in other.ranges.iter() {
+            self.insert_range(start, end);
+        }
+        if let Some(from) = other.from {
+            self.insert_from(from);
+        }
+        if other.largest {
+            self.insert_last();
+        }
+    }
+
+    fn with_from(mut self, start: u32) -> SequenceSet {
+        self.insert_from(start);
+        self
+    }
+
+    /// Merges ranges that reach the open-ended range into it.
+    fn normalize(&mut self) {
+        if let Some(mut from) = self.from {
+            while let Some(&(s, e)) = self.ranges.last() {
+                if e.saturating_add(1) < from {
+                    break;
+                }
+                from = if s < from { s } else { from };
+                self.ranges.pop();
+            }
+            self.from = Some(from);
+            self.largest = false;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.from.is_none() && !self.largest
+    }
+
+    /// Returns all numbers in the set, or `None` if it contains `*`, whose value only the server
+    /// knows.
+    pub fn numbers(&self) -> Option<Vec<u32>> {
+        if self.from.is_some() || self.largest {
+            return None;
+        }
+        let mut numbers = Vec::new();
+        for &(s, e) in self.ranges.iter() {
+            numbers.extend(s..e);
+            numbers.push(e);
+        }
+        Some(numbers)
+    }
+
+    /// Splits the set into sets whose textual form is at most `max_len` characters long, so that
+    /// commands stay below the command length limits of servers. A single range is never split.
+    pub fn chunks(&self, max_len: usize) -> Vec<SequenceSet> {
+        let mut chunks = Vec::new();
+        let mut chunk = SequenceSet::new();
+        let mut len = 0;
+        for item in self.items() {
+            let item_len = item.to_string().len();
+            if !chunk.is_empty() && len + 1 + item_len > max_len {
+                chunks.push(chunk);
+                chunk = SequenceSet::new();
+                len = 0;
+            }
+            len += if chunk.is_empty() { item_len } else { item_len + 1 };
+            match item {
+                Item::Range(s, e) => chunk.insert_range(s, e),
+                Item::From(s) => chunk.insert_from(s),
+                Item::Largest => chunk.insert_last()
+            }
+        }
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    fn items(&self) -> Vec<Item> {
+        let mut items: Vec<Item> = self.ranges.iter().map(|&(s, e)| Item::Range(s, e)).collect();
+        if let Some(from) = self.from {
+            items.push(Item::From(from));
+        }
+        if self.largest {
+            items.push(Item::Largest);
+        }
+        items
+    }
+}
+
+enum Item {
+    Range(u32, u32),
+    From(u32),
+    Largest
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Item::Range(s, e) if s == e => write!(f, "{}", s),
+            Item::Range(s, e) => write!(f, "{}:{}", s, e),
+            Item::From(s) => write!(f, "{}:*", s),
+            Item::Largest => f.write_str("*")
+        }
+    }
+}
+
+impl fmt::Display for SequenceSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, item) in self.items().iter().enumerate() {
+            if i > 0 {
+                try!(f.write_str(","));
+            }
+            try!(fmt::Display::fmt(item, f));
+        }
+        Ok(())
+    }
+}
+
+/// Parses a set in IMAP syntax, e.g. `"1:3,5,7:*"`.
+impl FromStr for SequenceSet {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<SequenceSet, ()> {
+        let mut set = SequenceSet::new();
+        for item in s.split(',') {
+            let mut bounds = item.splitn(2, ':');
+            let start = try!(bounds.next().ok_or(()));
+            match (start, bounds.next()) {
+                ("*", None) => set.insert_last(),
+                ("*", Some("*")) => set.insert_last(),
+                ("*", Some(end)) | (end, Some("*")) => set.insert_from(try!(end.parse().map_err(|_| ()))),
+                (number, None) => set.insert(try!(number.parse().map_err(|_| ()))),
+                (start, Some(end)) => {
+                    set.insert_range(try!(start.parse().map_err(|_| ())), try!(end.parse().map_err(|_| ())))
+                }
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl FromIterator<u32> for SequenceSet {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> SequenceSet {
+        let mut numbers: Vec<u32> = iter.into_iter().collect();
+        numbers.sort();
+        let mut set = SequenceSet::new();
+        for number in numbers {
+            match set.ranges.last_mut() {
+                Some(&mut (_, ref mut end)) if number <= end.saturating_add(1) => {
+                    if number > *end {
+                        *end = number;
+                    }
+                    continue;
+                },
+                _ => {}
+            }
+            set.ranges.push((number, number));
+        }
+        set
+    }
+}
+
+impl From<u32> for SequenceSet {
+    fn from(number: u32) -> SequenceSet {
+        let mut set = SequenceSet::new();
+        set.insert(number);
+        set
+    }
+}
+
+/// Converts a half-open range, i.e. `1..4` gives `1:3`.
+impl From<Range<u32>> for SequenceSet {
+    fn from(range: Range<u32>) -> SequenceSet {
+        let mut set = SequenceSet::new();
+        if range.start < range.end {
+            set.insert_range(range.start, range.end - 1);
+        }
+        set
+    }
+}
+
+/// Converts an open-ended range, i.e. `5..` gives `5:*`.
+impl From<RangeFrom<u32>> for SequenceSet {
+    fn from(range: RangeFrom<u32>) -> SequenceSet {
+        SequenceSet::new().with_from(range.start)
+    }
+}
+
+impl From<Vec<u32>> for SequenceSet {
+    fn from(numbers: Vec<u32>) -> SequenceSet {
+        numbers.into_iter().collect()
+    }
+}
+
+impl<'a> From<&'a [u32]> for SequenceSet {
+    fn from(numbers: &'a [u32]) -> SequenceSet {
+        numbers.iter().cloned().collect()
+    }
+}
+
+impl<'a> From<&'a SequenceSet> for SequenceSet {
+    fn from(set: &'a SequenceSet) -> SequenceSet {
+        set.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compress() {
+        let set: SequenceSet = vec![7, 1, 2, 3, 5, 3, 8, 9, 20].into_iter().collect();
+        assert!(set.to_string() == "1:3,5,7:9,20", "Unexpected set {}", set);
+        assert!(set.numbers() == Some(vec![1, 2, 3, 5, 7, 8, 9, 20]), "Unexpected numbers");
+    }
+
+    #[test]
+    fn insert() {
+        let mut set = SequenceSet::from(5);
+        set.insert_range(1, 3);
+        set.insert(4);
+        set.insert_range(10, 8);
+        assert!(set.to_string() == "1:5,8:10", "Unexpected set {}", set);
+        set.insert_from(11);
+        assert!(set.to_string() == "1:5,8:*", "Unexpected set {}", set);
+        assert!(set.numbers() == None, "Numbers of an open-ended set are unknown");
+        set.insert_last();
+        assert!(set.to_string() == "1:5,8:*", "Unexpected set {}", set);
+        assert!(SequenceSet::all().to_string() == "1:*", "Unexpected set");
+        assert!(SequenceSet::last().to_string() == "*", "Unexpected set");
+        assert!(SequenceSet::from(2..5).to_string() == "2:4", "Unexpected set");
+    }
+
+    #[test]
+    fn parse() {
+        let set: SequenceSet = "2,4:7,9,12:*".parse().unwrap();
+        assert!(set.to_string() == "2,4:7,9,12:*", "Unexpected set {}", set);
+        assert!("*".parse::<SequenceSet>().unwrap() == SequenceSet::last(), "Unexpected set");
+        assert!("1,x".parse::<SequenceSet>().is_err(), "Invalid set should be rejected");
+    }
+
+    #[test]
+    fn chunks() {
+        let set: SequenceSet = vec![1, 3, 5, 7, 9, 11, 20, 21, 22].into_iter().collect();
+        let chunks: Vec<String> = set.chunks(8).iter().map(|chunk| chunk.to_string()).collect();
+        assert!(chunks == vec!["1,3,5,7", "9,11", "20:22"], "Unexpected chunks {:?}", chunks);
+    }
+}
@@ -0,0 +1,102 @@
+use super::error::Result;
+use super::flag::{Flag, flag_list};
+
+/// How the flags of a STORE command change the flags of the messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StoreMode {
+    Add,
+    Remove,
+    Replace
+}
+
+/// A builder for the arguments of the STORE command.
+///
+/// ```no_run
+/// # use imap::flag::Flag;
+/// # use imap::store::StoreQuery;
+/// let query = StoreQuery::add(&[Flag::Deleted]).silent();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreQuery {
+    mode: StoreMode,
+    flags: Vec<Flag>,
+    silent: bool,
+    unchanged_since: Option<u64>
+}
+
+impl StoreQuery {
+    /// Adds the flags to the messages (`+FLAGS`).
+    pub fn add(flags: &[Flag]) -> StoreQuery {
+        StoreQuery::new(StoreMode::Add, flags)
+    }
+
+    /// Removes the flags from the messages (`-FLAGS`).
+    pub fn remove(flags: &[Flag]) -> StoreQuery {
+        StoreQuery::new(StoreMode::Remove, flags)
+    }
+
+    /// Replaces the flags of the messages (`FLAGS`).
+    pub fn replace(flags: &[Flag]) -> StoreQuery {
+        StoreQuery::new(StoreMode::Replace, flags)
+    }
+
+    /// Tells the server not to send the updated flags back.
+    pub fn silent(mut self) -> StoreQuery {
+        self.silent = true;
+        self
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.silent
+    }
+
+    /// Only changes messages whose mod-sequence is not higher than `mod_seq` (`UNCHANGEDSINCE`).
+    /// Requires CONDSTORE (RFC 7162). The messages that were left alone are returned as
+    /// `ResponseCode::Modified` by `Client::response_codes`.
+    pub fn unchanged_since(mut self, mod_seq: u64) -> StoreQuery {
+        self.unchanged_since = Some(mod_seq);
+        self
+    }
+
+    /// Returns the arguments of the STORE command, e.g. `+FLAGS.SILENT (\Deleted)` or
+    /// `(UNCHANGEDSINCE 320162338) +FLAGS (\Seen)`. Fails if a keyword is not valid.
+    pub fn to_arguments(&self) -> Result<String> {
+        let mode = match self.mode {
+            StoreMode::Add => "+",
+            StoreMode::Remove => "-",
+            StoreMode::Replace => ""
+        };
+        let silent = if self.silent { ".SILENT" } else { "" };
+        let modifier = match self.unchanged_since {
+            Some(mod_seq) => format!("(UNCHANGEDSINCE {}) ", mod_seq),
+            None => String::new()
+        };
+        Ok(format!("{}{}FLAGS{} {}", modifier, mode, silent, try!(flag_list(&self.flags))))
+    }
+
+    fn new(mode: StoreMode, flags: &[Flag]) -> StoreQuery {
+        StoreQuery {
+            mode: mode,
+            flags: flags.to_vec(),
+            silent: false,
+            unchanged_since: None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let query = StoreQuery::add(&[Flag::Deleted]);
+        assert!(query.to_arguments().unwrap() == "+FLAGS (\\Deleted)", "Unexpected arguments {:?}", query.to_arguments());
+        let query = StoreQuery::remove(&[Flag::Seen, Flag::Flagged]).silent();
+        assert!(query.to_arguments().unwrap() == "-FLAGS.SILENT (\\Seen \\Flagged)", "Unexpected arguments {:?}", query.to_arguments());
+        let query = StoreQuery::replace(&[]);
+        assert!(query.to_arguments().unwrap() == "FLAGS ()", "Unexpected arguments {:?}", query.to_arguments());
+        let query = StoreQuery::add(&[Flag::Seen]).unchanged_since(320162338);
+        assert!(query.to_arguments().unwrap() == "(UNCHANGEDSINCE 320162338) +FLAGS (\\Seen)", "Unexpected arguments {:?}", query.to_arguments());
+    }
+}
@@ -0,0 +1,129 @@
+//! The modified UTF-7 encoding of mailbox names (RFC 3501, section 5.1.3).
+//!
+//! ```
+//! # use imap::utf7;
+//! assert_eq!(utf7::encode("Entwürfe"), "Entw&APw-rfe");
+//! assert_eq!(utf7::decode("Entw&APw-rfe"), Some(String::from("Entwürfe")));
+//! ```
+
+static ALPHABET: &'static [u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+,";
+
+/// Encodes a mailbox name in modified UTF-7.
+pub fn encode(name: &str) -> String {
+    let mut encoded = String::new();
+    let mut shifted: Vec<u16> = Vec::new();
+    for c in name.chars() {
+        if c >= ' ' && c <= '~' {
+            if !shifted.is_empty() {
+                encode_shifted(&shifted, &mut encoded);
+                shifted.clear();
+            }
+            if c == '&' {
+                encoded.push_str("&-");
+            } else {
+                encoded.push(c);
+            }
+        } else {
+            let mut buf = [0; 2];
+            shifted.extend_from_slice(c.encode_utf16(&mut buf));
+        }
+    }
+    if !shifted.is_empty() {
+        encode_shifted(&shifted, &mut encoded);
+    }
+    encoded
+}
+
+/// Decodes a mailbox name from modified UTF-7, or returns `None` if it is not validly encoded.
+pub fn decode(name: &str) -> Option<String> {
+    let mut decoded = String::new();
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '&' {
+            decoded.push(c);
+            continue;
+        }
+        let mut shifted = String::new();
+        loop {
+            match chars.next() {
+                Some('-') => break,
+                Some(c) => shifted.push(c),
+                None => return None
+            }
+        }
+        if shifted.is_empty() {
+            decoded.push('&');
+        } else {
+            match decode_shifted(&shifted) {
+                Some(s) => decoded.push_str(&s),
+                None => return None
+            }
+        }
+    }
+    Some(decoded)
+}
+
+/// Appends UTF-16 code units as `&...-`, in base64 with `,` instead of `/` and without padding.
+fn encode_shifted(units: &[u16], encoded: &mut String) {
+    let mut bytes = Vec::with_capacity(units.len() * 2);
+    for &unit in units.iter() {
+        bytes.push((unit >> 8) as u8);
+        bytes.push(unit as u8);
+    }
+    encoded.push('&');
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let sextets = [b[0] >> 2, (b[0] & 0x03) << 4 | b[1] >> 4, (b[1] & 0x0f) << 2 | b[2] >> 6, b[2] & 0x3f];
+        for &sextet in sextets[..chunk.len() + 1].iter() {
+            encoded.push(ALPHABET[sextet as usize] as char);
+        }
+    }
+    encoded.push('-');
+}
+
+fn decode_shifted(shifted: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    for c in shifted.bytes() {
+        let sextet = match ALPHABET.iter().position(|&a| a == c) {
+            Some(sextet) => sextet as u32,
+            None => return None
+        };
+        bits = bits << 6 | sextet;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    if bytes.len() % 2 != 0 || bits != 0 {
+        return None;
+    }
+    let units: Vec<u16> = bytes.chunks(2).map(|pair| (pair[0] as u16) << 8 | pair[1] as u16).collect();
+    String::from_utf16(&units).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_names() {
+        assert!(encode("INBOX") == "INBOX", "ASCII names should not be encoded");
+        assert!(encode("Tom & Jerry") == "Tom &- Jerry", "Ampersand should be escaped");
+        assert!(encode("送信済み") == "&kAFP4W4IMH8-", "Unexpected encoding {}", encode("送信済み"));
+        assert!(encode("~peter/mail/台北/日本語") == "~peter/mail/&U,BTFw-/&ZeVnLIqe-",
+            "Unexpected encoding {}", encode("~peter/mail/台北/日本語"));
+    }
+
+    #[test]
+    fn decode_names() {
+        assert!(decode("Tom &- Jerry") == Some(String::from("Tom & Jerry")), "Ampersand should be unescaped");
+        assert!(decode("&kAFP4W4IMH8-") == Some(String::from("送信済み")), "Unexpected decoding");
+        assert!(decode("~peter/mail/&U,BTFw-/&ZeVnLIqe-") == Some(String::from("~peter/mail/台北/日本語")), "Unexpected decoding");
+        assert!(decode("Broken &U,BTF") == None, "Unterminated shift should be rejected");
+        assert!(decode("&!!-") == None, "Invalid base64 should be rejected");
+    }
+}